@@ -1,38 +1,67 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt::Write as _,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use etrace::some_or;
+use glob::Pattern;
+use regex::Regex;
 use rustc_abi::VariantIdx;
+use rustc_ast::{AttrKind, Attribute};
 use rustc_hir::{
     def::{DefKind, Res},
     intravisit::Visitor as HVisitor,
-    Expr, ExprKind, HirId, QPath,
+    BodyId, Expr, ExprKind, HirId, ImplItemKind, ItemKind, Mutability, QPath,
 };
 use rustc_index::bit_set::BitSet;
+use rustc_interface::Config;
 use rustc_middle::{
     hir::nested_filter,
-    mir::{BasicBlock, Body, Local, Location, TerminatorKind},
-    ty::{AdtKind, Ty, TyCtxt, TyKind, TypeAndMut},
+    mir::{
+        pretty::write_mir_pretty, BasicBlock, BinOp, Body, Local, Location, Operand, Rvalue,
+        StatementKind, TerminatorKind,
+    },
+    ty::{AdtKind, Ty, TyCtxt, TyKind, TypeAndMut, TypeckResults},
 };
 use rustc_session::config::Input;
-use rustc_span::{def_id::DefId, source_map::SourceMap, Span};
+use rustc_span::{def_id::DefId, source_map::SourceMap, sym, symbol::kw, Span};
+use rustc_target::spec::abi::Abi;
 use serde::{Deserialize, Serialize};
 
 use super::{domains::*, semantics::TransferedTerminator};
 use crate::{
-    rustc_data_structures::graph::WithSuccessors as _, rustc_mir_dataflow::Analysis as _, *,
+    compile_util::CompilerError, rustc_data_structures::graph::WithSuccessors as _,
+    rustc_mir_dataflow::Analysis as _, *,
 };
 
 #[derive(Debug, Clone)]
 pub struct AnalysisConfig {
     pub max_loop_head_states: usize,
     pub widening: bool,
-    pub verbose: bool,
     pub print_functions: BTreeSet<String>,
+    /// If set, the analysis time of each function is logged and recorded in its output params,
+    /// and this many of the slowest functions are printed.
     pub function_times: Option<usize>,
+    /// Functions with fewer output params are not reported.
+    pub min_output_params: usize,
+    /// Only functions whose paths match this are reported.
+    pub function_filter: Option<Regex>,
+    /// Only functions defined in files matching this are reported.
+    pub file_filter: Option<Pattern>,
+    /// If true, `&mut T` params are considered as well as raw pointers.
+    pub mut_refs: bool,
+    /// If true, the crate is compiled with `cfg(test)`, so `#[test]` functions and test modules
+    /// are analyzed.
+    pub include_tests: bool,
+    /// If set, only the functions in this module (e.g., `crate::ffi`) are analyzed. Functions
+    /// outside it are treated like foreign functions at call sites.
+    pub module_prefix: Option<String>,
+    /// Functions in files larger than this many bytes are not analyzed.
+    pub max_file_size: Option<usize>,
+    /// Functions whose optimized MIR has more basic blocks than this are not analyzed.
+    pub max_function_size: Option<usize>,
+    pub compile: compile_util::CompileOptions,
 }
 
 impl Default for AnalysisConfig {
@@ -40,38 +69,387 @@ impl Default for AnalysisConfig {
         Self {
             max_loop_head_states: 1,
             widening: true,
-            verbose: false,
             print_functions: BTreeSet::new(),
             function_times: None,
+            min_output_params: 1,
+            function_filter: None,
+            file_filter: None,
+            mut_refs: false,
+            include_tests: false,
+            module_prefix: None,
+            max_file_size: None,
+            max_function_size: None,
+            compile: compile_util::CompileOptions::default(),
         }
     }
 }
 
+impl AnalysisConfig {
+    fn make_config(&self, input: Input) -> Config {
+        let mut config = compile_util::make_config_with(input, &self.compile);
+        config.opts.test = self.include_tests;
+        config
+    }
+
+    fn in_module(&self, def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+        let prefix = some_or!(&self.module_prefix, return true);
+        has_path_prefix(&tcx.def_path_str(def_id), prefix)
+    }
+
+    /// Returns why the function is too large to analyze.
+    fn size_limit_exceeded(&self, def_id: DefId, tcx: TyCtxt<'_>) -> Option<String> {
+        if let Some(max) = self.max_file_size {
+            let span = tcx.def_span(def_id);
+            let file = tcx.sess.source_map().lookup_source_file(span.lo());
+            let size = (file.end_pos - file.start_pos).0 as usize;
+            if size > max {
+                return Some(format!("its file has {} bytes", size));
+            }
+        }
+        if let Some(max) = self.max_function_size {
+            let blocks = tcx.optimized_mir(def_id).basic_blocks.len();
+            if blocks > max {
+                return Some(format!("its MIR has {} basic blocks", blocks));
+            }
+        }
+        None
+    }
+
+    fn is_analyzed(&self, def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+        self.in_module(def_id, tcx) && self.size_limit_exceeded(def_id, tcx).is_none()
+    }
+
+    fn is_reported(&self, name: &str, span: Span, source_map: &SourceMap) -> bool {
+        if let Some(re) = &self.function_filter {
+            if !re.is_match(name) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.file_filter {
+            let path = some_or!(compile_util::span_to_path(span, source_map), return false);
+            if !pattern.matches_path(&path) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 pub type AnalysisResult = BTreeMap<String, Vec<OutputParam>>;
 
-pub fn analyze_path(path: &Path, conf: &AnalysisConfig) -> AnalysisResult {
+/// Checks whether `name`, as in `def_path_str`, is `prefix` or an item in it. A leading
+/// `crate::` in `prefix` is ignored.
+pub fn has_path_prefix(name: &str, prefix: &str) -> bool {
+    let prefix = prefix.strip_prefix("crate::").unwrap_or(prefix);
+    prefix == "crate"
+        || name == prefix
+        || name
+            .strip_prefix(prefix)
+            .map_or(false, |rest| rest.starts_with("::"))
+}
+
+pub fn analyze_path(path: &Path, conf: &AnalysisConfig) -> Result<AnalysisResult, CompilerError> {
     analyze_input(compile_util::path_to_input(path), conf)
 }
 
-pub fn analyze_code(code: &str, conf: &AnalysisConfig) -> AnalysisResult {
+pub fn analyze_code(code: &str, conf: &AnalysisConfig) -> Result<AnalysisResult, CompilerError> {
     analyze_input(compile_util::str_to_input(code), conf)
 }
 
-pub fn analyze_input(input: Input, conf: &AnalysisConfig) -> AnalysisResult {
-    let config = compile_util::make_config(input);
+/// Detects output parameters with the default configuration. The keys of the result are the
+/// `def_path_str`s of functions, which are what `transform` expects.
+pub fn detect_output_params(path: &Path) -> Result<AnalysisResult, CompilerError> {
+    analyze_path(path, &AnalysisConfig::default())
+}
+
+pub fn analyze_input(input: Input, conf: &AnalysisConfig) -> Result<AnalysisResult, CompilerError> {
+    let config = conf.make_config(input);
     compile_util::run_compiler(config, |tcx| {
-        analyze(tcx, conf)
+        reported_output_params(tcx, conf)
             .into_iter()
-            .filter_map(|(def_id, (_, params))| {
-                if params.is_empty() {
-                    None
-                } else {
-                    Some((tcx.def_path_str(def_id), params))
-                }
-            })
+            .map(|(_, name, params)| (name, params))
             .collect()
     })
-    .unwrap()
+}
+
+/// Returns the functions with output params, if any, without modifying any file, e.g., for a
+/// CI check.
+pub fn check_path(
+    path: &Path,
+    conf: &AnalysisConfig,
+) -> Result<Option<AnalysisResult>, CompilerError> {
+    check_input(compile_util::path_to_input(path), conf)
+}
+
+pub fn check_code(
+    code: &str,
+    conf: &AnalysisConfig,
+) -> Result<Option<AnalysisResult>, CompilerError> {
+    check_input(compile_util::str_to_input(code), conf)
+}
+
+pub fn check_input(
+    input: Input,
+    conf: &AnalysisConfig,
+) -> Result<Option<AnalysisResult>, CompilerError> {
+    let result = analyze_input(input, conf)?;
+    Ok((!result.is_empty()).then_some(result))
+}
+
+/// Returns the optimized MIR of the functions in `names`, or of every function if `names` is
+/// empty.
+pub fn mir_path(
+    path: &Path,
+    names: &BTreeSet<String>,
+    conf: &AnalysisConfig,
+) -> Result<String, CompilerError> {
+    mir_input(compile_util::path_to_input(path), names, conf)
+}
+
+pub fn mir_code(
+    code: &str,
+    names: &BTreeSet<String>,
+    conf: &AnalysisConfig,
+) -> Result<String, CompilerError> {
+    mir_input(compile_util::str_to_input(code), names, conf)
+}
+
+pub fn mir_input(
+    input: Input,
+    names: &BTreeSet<String>,
+    conf: &AnalysisConfig,
+) -> Result<String, CompilerError> {
+    let config = conf.make_config(input);
+    compile_util::run_compiler(config, |tcx| {
+        let mut mir = vec![];
+        for def_id in tcx.hir().body_owners() {
+            let def_id = def_id.to_def_id();
+            if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+                || !conf.in_module(def_id, tcx)
+            {
+                continue;
+            }
+            if names.is_empty() || names.contains(&tcx.def_path_str(def_id)) {
+                // writing to a `Vec` does not fail
+                write_mir_pretty(tcx, Some(def_id), &mut mir).unwrap();
+            }
+        }
+        String::from_utf8(mir).unwrap()
+    })
+}
+
+/// Analysis results grouped by the file and the (1-based) line where each function starts.
+pub type LocationMap = BTreeMap<(PathBuf, usize), AnalysisResult>;
+
+pub fn location_map(path: &Path) -> Result<LocationMap, CompilerError> {
+    location_map_input(
+        compile_util::path_to_input(path),
+        &AnalysisConfig::default(),
+    )
+}
+
+pub fn location_map_input(
+    input: Input,
+    conf: &AnalysisConfig,
+) -> Result<LocationMap, CompilerError> {
+    let config = conf.make_config(input);
+    compile_util::run_compiler(config, |tcx| {
+        let source_map = tcx.sess.source_map();
+        let mut map: LocationMap = BTreeMap::new();
+        for (def_id, name, params) in reported_output_params(tcx, conf) {
+            let span = tcx.def_span(def_id);
+            let file = some_or!(compile_util::span_to_path(span, source_map), continue);
+            let line = source_map.lookup_char_pos(span.lo()).line;
+            map.entry((file, line)).or_default().insert(name, params);
+        }
+        map
+    })
+}
+
+/// Functions with output params and their direct callers.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallGraph {
+    /// Maps each function in the analysis result to the functions calling it.
+    pub edges: BTreeMap<String, Vec<String>>,
+}
+
+impl CallGraph {
+    pub fn build(result: &AnalysisResult, tcx: TyCtxt<'_>) -> Self {
+        let hir = tcx.hir();
+        let mut edges: BTreeMap<_, BTreeSet<_>> = result
+            .keys()
+            .map(|name| (name.clone(), BTreeSet::new()))
+            .collect();
+        for def_id in hir.body_owners() {
+            let mut visitor = CallVisitor::new(tcx);
+            visitor.visit_body(hir.body(hir.body_owned_by(def_id)));
+            // calls in closures are attributed to the enclosing function
+            let caller = tcx.def_path_str(tcx.typeck_root_def_id(def_id.to_def_id()));
+            for callee in visitor.callees {
+                if let Some(callers) = edges.get_mut(&tcx.def_path_str(callee)) {
+                    callers.insert(caller.clone());
+                }
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .map(|(callee, callers)| (callee, callers.into_iter().collect()))
+            .collect();
+        Self { edges }
+    }
+
+    /// Graphviz DOT with an edge from each caller to its callee.
+    pub fn to_dot(&self) -> String {
+        let mut s = "digraph {\n".to_string();
+        for (callee, callers) in &self.edges {
+            writeln!(s, "    {:?};", callee).unwrap();
+            for caller in callers {
+                writeln!(s, "    {:?} -> {:?};", caller, callee).unwrap();
+            }
+        }
+        s.push_str("}\n");
+        s
+    }
+}
+
+pub fn call_graph_path(
+    path: &Path,
+    result: &AnalysisResult,
+    conf: &AnalysisConfig,
+) -> Result<CallGraph, CompilerError> {
+    call_graph_input(compile_util::path_to_input(path), result, conf)
+}
+
+pub fn call_graph_code(
+    code: &str,
+    result: &AnalysisResult,
+    conf: &AnalysisConfig,
+) -> Result<CallGraph, CompilerError> {
+    call_graph_input(compile_util::str_to_input(code), result, conf)
+}
+
+pub fn call_graph_input(
+    input: Input,
+    result: &AnalysisResult,
+    conf: &AnalysisConfig,
+) -> Result<CallGraph, CompilerError> {
+    let config = conf.make_config(input);
+    compile_util::run_compiler(config, |tcx| CallGraph::build(result, tcx))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counts {
+    pub functions: usize,
+    pub with_output_params: usize,
+    pub musts: usize,
+    pub mays: usize,
+}
+
+impl Counts {
+    fn add(&mut self, params: &[OutputParam]) {
+        self.functions += 1;
+        if !params.is_empty() {
+            self.with_output_params += 1;
+        }
+        self.musts += params.iter().filter(|p| p.must).count();
+        self.mays += params.iter().filter(|p| !p.must).count();
+    }
+}
+
+/// Summary of the reported output params of all the analyzed functions.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub total: Counts,
+    /// The number of functions excluded from the report, by the reason.
+    pub skipped: BTreeMap<String, usize>,
+    pub files: BTreeMap<PathBuf, Counts>,
+    /// Analysis times in milliseconds, slowest first. Empty unless `function_times` is set.
+    pub analysis_times: Vec<(String, u64)>,
+}
+
+pub fn stats_path(path: &Path, conf: &AnalysisConfig) -> Result<Stats, CompilerError> {
+    stats_input(compile_util::path_to_input(path), conf)
+}
+
+pub fn stats_code(code: &str, conf: &AnalysisConfig) -> Result<Stats, CompilerError> {
+    stats_input(compile_util::str_to_input(code), conf)
+}
+
+pub fn stats_input(input: Input, conf: &AnalysisConfig) -> Result<Stats, CompilerError> {
+    let config = conf.make_config(input);
+    compile_util::run_compiler(config, |tcx| {
+        let source_map = tcx.sess.source_map();
+        let fn_ptrs = fn_ptrs(tcx);
+
+        let mut stats = Stats::default();
+        let (results, times) = analyze_timed(tcx, conf);
+        if let Some(n) = conf.function_times {
+            let mut times: Vec<_> = times.into_iter().collect();
+            times.sort_by_key(|(_, t)| u128::MAX - *t);
+            stats.analysis_times = times
+                .into_iter()
+                .take(n)
+                .map(|(def_id, t)| (tcx.def_path_str(def_id), t as u64))
+                .collect();
+        }
+        for (def_id, (_, params)) in results {
+            let params = if let Some(reason) = skip_reason(def_id, &params, &fn_ptrs, tcx, conf) {
+                *stats.skipped.entry(reason.to_string()).or_default() += 1;
+                vec![]
+            } else {
+                params
+            };
+            stats.total.add(&params);
+            let span = tcx.def_span(def_id);
+            if let Some(file) = compile_util::span_to_path(span, source_map) {
+                stats.files.entry(file).or_default().add(&params);
+            }
+        }
+        stats
+    })
+}
+
+fn reported_output_params(
+    tcx: TyCtxt<'_>,
+    conf: &AnalysisConfig,
+) -> Vec<(DefId, String, Vec<OutputParam>)> {
+    let fn_ptrs = fn_ptrs(tcx);
+    analyze(tcx, conf)
+        .into_iter()
+        .filter_map(|(def_id, (_, params))| {
+            if params.is_empty() || skip_reason(def_id, &params, &fn_ptrs, tcx, conf).is_some() {
+                None
+            } else {
+                Some((def_id, tcx.def_path_str(def_id), params))
+            }
+        })
+        .collect()
+}
+
+/// Returns why the output params of a function are not reported.
+fn skip_reason(
+    def_id: DefId,
+    params: &[OutputParam],
+    fn_ptrs: &BTreeSet<DefId>,
+    tcx: TyCtxt<'_>,
+    conf: &AnalysisConfig,
+) -> Option<&'static str> {
+    if params.is_empty() {
+        None
+    } else if fn_ptrs.contains(&def_id) {
+        // its signature must match the function pointer type
+        Some("address taken")
+    } else if params.len() < conf.min_output_params {
+        Some("too few output params")
+    } else if !conf.is_reported(
+        &tcx.def_path_str(def_id),
+        tcx.def_span(def_id),
+        tcx.sess.source_map(),
+    ) {
+        Some("filtered out")
+    } else {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -85,25 +463,74 @@ pub fn analyze(
     tcx: TyCtxt<'_>,
     conf: &AnalysisConfig,
 ) -> BTreeMap<DefId, (FunctionSummary, Vec<OutputParam>)> {
+    analyze_timed(tcx, conf).0
+}
+
+/// Also returns the analysis time of each function in milliseconds.
+#[allow(clippy::type_complexity)]
+fn analyze_timed(
+    tcx: TyCtxt<'_>,
+    conf: &AnalysisConfig,
+) -> (
+    BTreeMap<DefId, (FunctionSummary, Vec<OutputParam>)>,
+    BTreeMap<DefId, u128>,
+) {
     let hir = tcx.hir();
 
     let mut call_graph = BTreeMap::new();
     let mut inputs_map = BTreeMap::new();
+    let mut captures_map = BTreeMap::new();
     for id in hir.items() {
         let item = hir.item(id);
-        if item.ident.name.to_ident_string() == "main" {
-            continue;
+        match &item.kind {
+            ItemKind::Fn(sig, _, body_id) => {
+                if item.ident.name.to_ident_string() == "main" {
+                    continue;
+                }
+                let def_id = item.item_id().owner_id.def_id.to_def_id();
+                if !conf.in_module(def_id, tcx) {
+                    continue;
+                }
+                if let Some(reason) = conf.size_limit_exceeded(def_id, tcx) {
+                    let name = tcx.def_path_str(def_id);
+                    tracing::warn!("{} is not analyzed because {}", name, reason);
+                    continue;
+                }
+                inputs_map.insert(def_id, sig.decl.inputs.len());
+                let mut visitor = CallVisitor::new(tcx);
+                visitor.visit_item(item);
+                captures_map.insert(def_id, captured_params(*body_id, &visitor.captures, tcx));
+                call_graph.insert(def_id, visitor.callees);
+            }
+            ItemKind::Impl(imp) => {
+                for impl_item_ref in imp.items {
+                    let impl_item = hir.impl_item(impl_item_ref.id);
+                    let ImplItemKind::Fn(sig, body_id) = &impl_item.kind else {
+                        continue;
+                    };
+                    let def_id = impl_item.owner_id.def_id.to_def_id();
+                    // methods generated by derive macros (e.g., bitfields) are handled by
+                    // transfer_method_call
+                    if impl_item.span.from_expansion() || tcx.generics_of(def_id).count() != 0 {
+                        continue;
+                    }
+                    if !conf.in_module(def_id, tcx) {
+                        continue;
+                    }
+                    if let Some(reason) = conf.size_limit_exceeded(def_id, tcx) {
+                        let name = tcx.def_path_str(def_id);
+                        tracing::warn!("{} is not analyzed because {}", name, reason);
+                        continue;
+                    }
+                    inputs_map.insert(def_id, sig.decl.inputs.len());
+                    let mut visitor = CallVisitor::new(tcx);
+                    visitor.visit_impl_item(impl_item);
+                    captures_map.insert(def_id, captured_params(*body_id, &visitor.captures, tcx));
+                    call_graph.insert(def_id, visitor.callees);
+                }
+            }
+            _ => {}
         }
-        let inputs = if let rustc_hir::ItemKind::Fn(sig, _, _) = &item.kind {
-            sig.decl.inputs.len()
-        } else {
-            continue;
-        };
-        let def_id = item.item_id().owner_id.def_id.to_def_id();
-        inputs_map.insert(def_id, inputs);
-        let mut visitor = CallVisitor::new(tcx);
-        visitor.visit_item(item);
-        call_graph.insert(def_id, visitor.callees);
     }
 
     let funcs: BTreeSet<_> = call_graph.keys().cloned().collect();
@@ -117,27 +544,28 @@ pub fn analyze(
         .flatten()
         .collect();
 
-    let mut visitor = FnPtrVisitor::new(tcx);
-    tcx.hir().visit_all_item_likes_in_crate(&mut visitor);
-
     let info_map: BTreeMap<_, _> = funcs
         .iter()
         .map(|def_id| {
             let inputs = inputs_map[def_id];
             let body = tcx.optimized_mir(def_id);
-            let param_tys = get_param_tys(body, inputs, tcx);
+            let param_tys = get_param_tys(body, inputs, conf.mut_refs, tcx);
             let pre_rpo_map = get_rpo_map(body);
             let loop_blocks = get_loop_blocks(body, &pre_rpo_map);
             let rpo_map = compute_rpo_map(body, &loop_blocks);
             let dead_locals = get_dead_locals(body, tcx);
-            let fn_ptr = visitor.fn_ptrs.contains(def_id);
+            let abi_sensitive = is_abi_sensitive(*def_id, tcx);
+            let captured_params = captures_map.remove(def_id).unwrap();
+            let volatile_params = volatile_params(body, inputs, tcx);
             let info = FuncInfo {
                 inputs,
                 param_tys,
                 loop_blocks,
                 rpo_map,
                 dead_locals,
-                fn_ptr,
+                abi_sensitive,
+                captured_params,
+                volatile_params,
             };
             (*def_id, info)
         })
@@ -172,14 +600,12 @@ pub fn analyze(
 
                 let mut analyzer = Analyzer::new(tcx, &info_map[def_id], conf, &summaries);
                 let body = tcx.optimized_mir(*def_id);
-                if conf.verbose {
-                    println!(
-                        "{:?} {} {}",
-                        def_id,
-                        body.basic_blocks.len(),
-                        body.local_decls.len()
-                    );
-                }
+                tracing::debug!(
+                    "{:?} {} {}",
+                    def_id,
+                    body.basic_blocks.len(),
+                    body.local_decls.len()
+                );
 
                 let AnalyzedBody {
                     states,
@@ -199,10 +625,16 @@ pub fn analyze(
                     .flat_map(|p| analyzer.expands_path(&AbsPath(vec![*p])))
                     .collect();
 
-                let mut return_states = return_location(body)
-                    .and_then(|ret| states.get(&ret))
-                    .cloned()
-                    .unwrap_or_default();
+                let mut return_states: BTreeMap<_, AbsState> = BTreeMap::new();
+                for ret in return_locations(body) {
+                    for (k, st) in states.get(&ret).into_iter().flatten() {
+                        let st = match return_states.get(k) {
+                            Some(prev) => prev.join(st),
+                            None => st.clone(),
+                        };
+                        return_states.insert(k.clone(), st);
+                    }
+                }
                 for st in return_states.values_mut() {
                     st.writes.remove(&nullable_params);
                     st.add_excludes(nullable_paths.iter().cloned());
@@ -228,6 +660,7 @@ pub fn analyze(
 
             if !need_rerun {
                 for def_id in def_ids {
+                    let start = std::time::Instant::now();
                     let mut analyzer = Analyzer::new(tcx, &info_map[def_id], conf, &summaries);
                     analyzer.ptr_params = ptr_params_map.remove(def_id).unwrap();
                     let summary = &summaries[def_id];
@@ -239,17 +672,24 @@ pub fn analyze(
                     let result = results.remove(def_id).unwrap();
                     for p in &mut output_params {
                         analyzer.find_complete_write(p, &result, &writes_map, &call_args, *def_id);
+                        analyzer.find_loop_write(p, &writes_map);
                     }
                     output_params_map.insert(*def_id, output_params);
+
+                    let t = analysis_times.entry(*def_id).or_default();
+                    *t += start.elapsed().as_millis();
+                    if conf.function_times.is_some() {
+                        tracing::info!("{}: {}ms", tcx.def_path_str(*def_id), t);
+                    }
                 }
                 break;
             }
         }
     }
     if let Some(n) = &conf.function_times {
-        let mut analysis_times: Vec<_> = analysis_times.into_iter().collect();
+        let mut analysis_times: Vec<_> = analysis_times.iter().collect();
         analysis_times.sort_by_key(|(_, t)| u128::MAX - *t);
-        for (def_id, t) in analysis_times.iter().take(*n) {
+        for (def_id, t) in analysis_times.into_iter().take(*n) {
             let f = tcx.def_path(*def_id).to_string_no_crate_verbose();
             let body = tcx.optimized_mir(*def_id);
             let blocks = body.basic_blocks.len();
@@ -258,13 +698,19 @@ pub fn analyze(
         }
     }
 
-    summaries
+    let results = summaries
         .into_iter()
         .map(|(def_id, summary)| {
-            let output_params = output_params_map.remove(&def_id).unwrap();
+            let mut output_params = output_params_map.remove(&def_id).unwrap();
+            if conf.function_times.is_some() {
+                for p in &mut output_params {
+                    p.analysis_time_ms = analysis_times.get(&def_id).map(|t| *t as u64);
+                }
+            }
             (def_id, (summary, output_params))
         })
-        .collect()
+        .collect();
+    (results, analysis_times)
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -277,9 +723,132 @@ pub struct CompleteWrite {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OutputParam {
     pub index: usize,
+    /// The name bound by the parameter pattern, if it is a plain binding.
+    pub name: Option<String>,
     pub must: bool,
     pub return_values: ReturnValues,
     pub complete_writes: Vec<CompleteWrite>,
+    /// The function is `extern` or `#[no_mangle]`, so changing its signature breaks its ABI.
+    pub abi_sensitive: bool,
+    /// The pointee is an integer and every write stores 0 or 1 into it.
+    pub bool_output: bool,
+    /// The function checks the param for null and does not write to it on the null path.
+    /// Such paths are ignored in computing `must`.
+    pub null_guarded: bool,
+    /// The pointee is an integer or `bool`, and this value is written on every path.
+    pub const_write: Option<i128>,
+    /// No path writes only some fields of the pointee. Only an annotated param can be reported
+    /// otherwise.
+    pub fully_initialized: bool,
+    /// The location of the parameter's type in the function signature.
+    pub location: SourceLocation,
+    /// The param is passed to `read_volatile` or `write_volatile`, e.g., for MMIO, so its
+    /// accesses must not be turned into return values.
+    pub volatile: bool,
+    /// Every write to the param happens inside a loop, e.g., accumulating into a buffer.
+    pub loop_write: bool,
+    /// The analysis time of the function in milliseconds, if `function_times` is set.
+    pub analysis_time_ms: Option<u64>,
+}
+
+impl std::fmt::Display for OutputParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.must { "must-write" } else { "may-write" };
+        write!(f, "param {}", self.index)?;
+        if let Some(name) = &self.name {
+            write!(f, " `{}`", name)?;
+        }
+        write!(f, " ({}", kind)?;
+        if self.null_guarded {
+            write!(f, ", null-guarded")?;
+        }
+        if self.volatile {
+            write!(f, ", volatile")?;
+        }
+        if self.loop_write {
+            write!(f, ", loop-written")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Displays an `AnalysisResult` with a line per function.
+pub struct DisplayResult<'a>(pub &'a AnalysisResult);
+
+impl std::fmt::Display for DisplayResult<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, params) in self.0 {
+            let params: Vec<_> = params.iter().map(|p| p.to_string()).collect();
+            writeln!(f, "fn {}: output params [{}]", name, params.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How easily the output params of a function can be turned into return values: 2 if all of
+/// them are must, 1 if every may param is written exactly when the function returns certain
+/// values, so that the return value can become a `Result`, and 0 otherwise.
+pub fn convertibility_score(params: &[OutputParam]) -> u8 {
+    if params.iter().all(|p| p.must) {
+        2
+    } else if params
+        .iter()
+        .all(|p| p.must || !matches!(p.return_values, ReturnValues::None))
+    {
+        1
+    } else {
+        0
+    }
+}
+
+/// Displays an `AnalysisResult` like `DisplayResult`, but in the descending order of
+/// `convertibility_score`.
+pub struct DisplayRanked<'a>(pub &'a AnalysisResult);
+
+impl std::fmt::Display for DisplayRanked<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut fns: Vec<_> = self.0.iter().collect();
+        fns.sort_by_key(|(_, params)| std::cmp::Reverse(convertibility_score(params)));
+        for (name, params) in fns {
+            let score = convertibility_score(params);
+            let params: Vec<_> = params.iter().map(|p| p.to_string()).collect();
+            writeln!(
+                f,
+                "fn {} (score {}): output params [{}]",
+                name,
+                score,
+                params.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `line` and `column` are 1-based; `lo` and `hi` are byte offsets in the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub lo: usize,
+    pub hi: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    fn from_span(span: Span, source_map: &SourceMap) -> Self {
+        let loc = source_map.lookup_char_pos(span.lo());
+        let file = &loc.file;
+        let name = compile_util::span_to_path(span, source_map)
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.name.prefer_remapped().to_string());
+        Self {
+            file: name,
+            lo: file.original_relative_byte_pos(span.lo()).0 as usize,
+            hi: file.original_relative_byte_pos(span.hi()).0 as usize,
+            line: loc.line,
+            column: loc.col_display + 1,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -297,7 +866,10 @@ struct FuncInfo {
     loop_blocks: BTreeMap<BasicBlock, BTreeSet<BasicBlock>>,
     rpo_map: BTreeMap<BasicBlock, usize>,
     dead_locals: Vec<BitSet<Local>>,
-    fn_ptr: bool,
+    abi_sensitive: bool,
+    /// Params captured by closures. Writes in closure bodies are not analyzed.
+    captured_params: BTreeSet<usize>,
+    volatile_params: BTreeSet<usize>,
 }
 
 impl FuncInfo {
@@ -341,6 +913,11 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
         }
     }
 
+    /// Checks whether `def_id` is excluded by the module prefix or the size limits.
+    pub fn is_excluded(&self, def_id: DefId) -> bool {
+        !self.conf.is_analyzed(def_id, self.tcx)
+    }
+
     fn get_return_ptrs(&self, summary: &FunctionSummary) -> BTreeSet<usize> {
         summary
             .return_states
@@ -391,7 +968,7 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
         return_ptrs: &BTreeSet<usize>,
         def_id: DefId,
     ) -> Vec<OutputParam> {
-        if self.info.fn_ptr || summary.return_states.values().any(|st| st.writes.is_bot()) {
+        if summary.return_states.values().any(|st| st.writes.is_bot()) {
             return vec![];
         }
 
@@ -409,10 +986,26 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
             .collect();
 
         let body = self.tcx.optimized_mir(def_id);
+        let annotations = param_annotations(def_id, self.tcx);
         let mut writes = vec![];
         for i in 1..=self.info.inputs {
+            let forced = match annotations[i - 1] {
+                Some(true) => true,
+                Some(false) => continue,
+                None => false,
+            };
+            if self.info.captured_params.contains(&i) {
+                tracing::info!(
+                    "param {} of {} is not reported because it is captured by a closure",
+                    i - 1,
+                    self.tcx.def_path_str(def_id)
+                );
+                continue;
+            }
+            // a write to any field of a union is recorded as a write to the whole union, which
+            // is unsound when the fields have different sizes
             if reads.contains(&i)
-                || excludes.contains(&i)
+                || (!forced && excludes.contains(&i))
                 || return_ptrs.contains(&i)
                 || self.info.param_tys[i] == TypeInfo::Union
             {
@@ -420,13 +1013,12 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
             }
 
             let ty = &body.local_decls[Local::from_usize(i)].ty;
-            let TyKind::RawPtr(TypeAndMut { ty, .. }) = ty.kind() else {
-                continue;
-            };
+            let ty = some_or!(pointee_ty(*ty, self.conf.mut_refs, self.tcx), continue);
             if ty.is_c_void(self.tcx) {
                 continue;
             }
 
+            let arg = self.ptr_params.iter().position(|p| *p == i).unwrap();
             let expanded: BTreeSet<_> = self
                 .expands_path(&AbsPath(vec![i]))
                 .into_iter()
@@ -458,15 +1050,20 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
                             Write::Partial
                         };
                         let rv = st.local.get(0).clone();
-                        Some((w, rv))
+                        let v = st.args.get(arg);
+                        Some((w, rv, is_zero_or_one(v, ty), constant_of(v, ty)))
                     }
                 })
                 .collect();
 
-            if wrs.iter().any(|(w, _)| *w == Write::All)
-                && wrs.iter().all(|(w, _)| *w != Write::Partial)
+            if wrs.iter().any(|(w, ..)| *w == Write::All)
+                && (forced || wrs.iter().all(|(w, ..)| *w != Write::Partial))
             {
-                writes.push((i, wrs));
+                let null_guarded = summary
+                    .return_states
+                    .values()
+                    .any(|st| st.nulls.contains(&AbsPath(vec![i])));
+                writes.push((i, wrs, null_guarded));
             }
         }
         if writes.is_empty() {
@@ -474,21 +1071,41 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
         }
 
         let ret_ty = &body.local_decls[Local::from_usize(0)].ty;
+        let decl = self
+            .tcx
+            .hir()
+            .get_if_local(def_id)
+            .unwrap()
+            .fn_decl()
+            .unwrap();
+        let names = self.tcx.fn_arg_names(def_id);
+        let source_map = self.tcx.sess.source_map();
         writes
             .into_iter()
-            .map(|(index, wrs)| {
-                let must = wrs.iter().all(|(w, _)| *w == Write::All);
+            .map(|(index, wrs, null_guarded)| {
+                let must = wrs.iter().all(|(w, ..)| *w == Write::All);
+                let fully_initialized = wrs.iter().all(|(w, ..)| *w != Write::Partial);
+                let bool_output = wrs
+                    .iter()
+                    .all(|(w, _, bool_like, _)| *w != Write::All || *bool_like);
+                let const_write = if must {
+                    let mut consts = wrs.iter().map(|(_, _, _, c)| *c);
+                    let first = consts.next().flatten();
+                    first.filter(|c| consts.all(|c2| c2 == Some(*c)))
+                } else {
+                    None
+                };
                 let return_values = if !must {
                     let (wst, nwst): (Vec<_>, Vec<_>) =
-                        wrs.into_iter().partition(|(w, _)| *w == Write::All);
+                        wrs.into_iter().partition(|(w, ..)| *w == Write::All);
                     let w = wst
                         .into_iter()
-                        .map(|(_, v)| v)
+                        .map(|(_, v, ..)| v)
                         .reduce(|a, b| a.join(&b))
                         .unwrap();
                     let nw = nwst
                         .into_iter()
-                        .map(|(_, v)| v)
+                        .map(|(_, v, ..)| v)
                         .reduce(|a, b| a.join(&b))
                         .unwrap();
                     match ret_ty.kind() {
@@ -500,11 +1117,23 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
                 } else {
                     ReturnValues::None
                 };
+                let name = names[index - 1];
                 OutputParam {
                     index: index - 1,
+                    name: (name.name != kw::Empty && name.name != kw::Underscore)
+                        .then(|| name.to_string()),
                     must,
                     return_values,
                     complete_writes: vec![],
+                    abi_sensitive: self.info.abi_sensitive,
+                    bool_output,
+                    null_guarded,
+                    const_write,
+                    fully_initialized,
+                    location: SourceLocation::from_span(decl.inputs[index - 1].span, source_map),
+                    volatile: self.info.volatile_params.contains(&index),
+                    loop_write: false,
+                    analysis_time_ms: None,
                 }
             })
             .collect()
@@ -583,6 +1212,24 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
         }
     }
 
+    fn find_loop_write(
+        &self,
+        param: &mut OutputParam,
+        writes_map: &BTreeMap<Location, BTreeSet<AbsPath>>,
+    ) {
+        let local = param.index + 1;
+        let mut blocks = writes_map
+            .iter()
+            .filter(|(_, writes)| writes.iter().any(|p| p.0[0] == local))
+            .map(|(location, _)| location.block)
+            .peekable();
+        if blocks.peek().is_none() {
+            return;
+        }
+        let loop_blocks: BTreeSet<_> = self.info.loop_blocks.values().flatten().collect();
+        param.loop_write = blocks.all(|bb| loop_blocks.contains(&bb));
+    }
+
     fn analyze_body(&mut self, body: &Body<'tcx>) -> AnalyzedBody {
         let mut start_state = AbsState::bot();
         start_state.writes = MustPathSet::top();
@@ -590,11 +1237,11 @@ impl<'a, 'tcx> Analyzer<'a, 'tcx> {
 
         for i in 1..=self.info.inputs {
             let ty = &body.local_decls[Local::from_usize(i)].ty;
-            let v = if let TyKind::RawPtr(TypeAndMut { ty, .. }) = ty.kind() {
-                let v = self.top_value_of_ty(ty);
+            let v = if let Some(pointee) = pointee_ty(*ty, self.conf.mut_refs, self.tcx) {
+                let v = self.top_value_of_ty(&pointee);
                 let idx = start_state.args.push(v);
                 self.ptr_params.push(i);
-                AbsValue::arg(idx)
+                param_value(*ty, AbsValue::arg(idx), self.tcx)
             } else {
                 self.top_value_of_ty(ty)
             };
@@ -832,6 +1479,8 @@ impl FunctionSummary {
 struct CallVisitor<'tcx> {
     tcx: TyCtxt<'tcx>,
     callees: BTreeSet<DefId>,
+    /// Variables captured by closures in the body.
+    captures: BTreeSet<HirId>,
 }
 
 impl<'tcx> CallVisitor<'tcx> {
@@ -839,8 +1488,106 @@ impl<'tcx> CallVisitor<'tcx> {
         Self {
             tcx,
             callees: BTreeSet::new(),
+            captures: BTreeSet::new(),
+        }
+    }
+
+    /// The owner of `expr` may have no body, e.g., a struct with an array length.
+    fn typeck_results(&self, expr: &Expr<'_>) -> &'tcx TypeckResults<'tcx> {
+        let owner = self.tcx.hir().enclosing_body_owner(expr.hir_id);
+        self.tcx.typeck(owner)
+    }
+}
+
+/// Returns the locals of the params whose bindings are in `captures`.
+fn captured_params(
+    body_id: BodyId,
+    captures: &BTreeSet<HirId>,
+    tcx: TyCtxt<'_>,
+) -> BTreeSet<usize> {
+    tcx.hir()
+        .body(body_id)
+        .params
+        .iter()
+        .enumerate()
+        .filter_map(|(i, param)| {
+            if captures.contains(&param.pat.hir_id) {
+                Some(i + 1)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the locals of the params from which the pointers passed to volatile reads and writes
+/// are derived.
+fn volatile_params<'tcx>(body: &Body<'tcx>, inputs: usize, tcx: TyCtxt<'tcx>) -> BTreeSet<usize> {
+    let mut volatile_ptrs = BTreeSet::new();
+    for bbd in body.basic_blocks.iter() {
+        let TerminatorKind::Call { func, args, .. } = &bbd.terminator().kind else {
+            continue;
+        };
+        let (callee, _) = some_or!(func.const_fn_def(), continue);
+        let name = tcx.item_name(callee);
+        if !matches!(
+            name.as_str(),
+            "read_volatile" | "write_volatile" | "volatile_load" | "volatile_store"
+        ) {
+            continue;
+        }
+        if let Some(place) = args.first().and_then(|arg| arg.place()) {
+            volatile_ptrs.insert(place.local);
         }
     }
+    if volatile_ptrs.is_empty() {
+        return BTreeSet::new();
+    }
+
+    // a pointer is derived from the locals it is copied, cast, or offset from
+    let mut sources: BTreeMap<Local, BTreeSet<Local>> = BTreeMap::new();
+    let locals = |op: &Operand<'_>| {
+        op.place()
+            .filter(|p| p.projection.is_empty())
+            .map(|p| p.local)
+    };
+    for bbd in body.basic_blocks.iter() {
+        for stmt in &bbd.statements {
+            let StatementKind::Assign(box (place, rvalue)) = &stmt.kind else {
+                continue;
+            };
+            let op = match rvalue {
+                Rvalue::Use(op) | Rvalue::Cast(_, op, _) => op,
+                Rvalue::BinaryOp(BinOp::Offset, box (op, _)) => op,
+                _ => continue,
+            };
+            if let Some(local) = locals(op) {
+                sources.entry(place.local).or_default().insert(local);
+            }
+        }
+        let TerminatorKind::Call {
+            args, destination, ..
+        } = &bbd.terminator().kind
+        else {
+            continue;
+        };
+        if body.local_decls[destination.local].ty.is_unsafe_ptr() {
+            let srcs = args.iter().filter_map(locals);
+            sources.entry(destination.local).or_default().extend(srcs);
+        }
+    }
+
+    let mut work_list: Vec<_> = volatile_ptrs.iter().copied().collect();
+    while let Some(local) = work_list.pop() {
+        for src in sources.get(&local).into_iter().flatten() {
+            if volatile_ptrs.insert(*src) {
+                work_list.push(*src);
+            }
+        }
+    }
+    (1..=inputs)
+        .filter(|i| volatile_ptrs.contains(&Local::from_usize(*i)))
+        .collect()
 }
 
 impl<'tcx> HVisitor<'tcx> for CallVisitor<'tcx> {
@@ -851,17 +1598,40 @@ impl<'tcx> HVisitor<'tcx> for CallVisitor<'tcx> {
     }
 
     fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
-        if let ExprKind::Call(callee, _) = expr.kind {
-            if let ExprKind::Path(QPath::Resolved(_, path)) = callee.kind {
-                if let Res::Def(DefKind::Fn, def_id) = path.res {
+        match expr.kind {
+            ExprKind::Call(callee, _) => {
+                if let ExprKind::Path(qpath) = &callee.kind {
+                    let typeck = self.typeck_results(expr);
+                    let res = typeck.qpath_res(qpath, callee.hir_id);
+                    if let Res::Def(DefKind::Fn | DefKind::AssocFn, def_id) = res {
+                        self.callees.insert(def_id);
+                    }
+                }
+            }
+            ExprKind::MethodCall(..) => {
+                let typeck = self.typeck_results(expr);
+                if let Some(def_id) = typeck.type_dependent_def_id(expr.hir_id) {
                     self.callees.insert(def_id);
                 }
             }
+            ExprKind::Closure(closure) => {
+                if let Some(upvars) = self.tcx.upvars_mentioned(closure.def_id) {
+                    self.captures.extend(upvars.keys());
+                }
+            }
+            _ => {}
         }
         rustc_hir::intravisit::walk_expr(self, expr);
     }
 }
 
+/// Returns the functions whose addresses are taken.
+fn fn_ptrs(tcx: TyCtxt<'_>) -> BTreeSet<DefId> {
+    let mut visitor = FnPtrVisitor::new(tcx);
+    tcx.hir().visit_all_item_likes_in_crate(&mut visitor);
+    visitor.fn_ptrs
+}
+
 struct FnPtrVisitor<'tcx> {
     tcx: TyCtxt<'tcx>,
     callees: BTreeSet<HirId>,
@@ -1002,6 +1772,7 @@ fn analysis_result_to_string(
                 statement_index,
             };
             if let Some(states) = states.get(&location) {
+                writeln!(&mut res, "must writes: {:?}", must_writes_at(states))?;
                 for state in states.values() {
                     writeln!(&mut res, "{:?}", state)?;
                 }
@@ -1017,6 +1788,7 @@ fn analysis_result_to_string(
                 statement_index: bbd.statements.len(),
             };
             if let Some(states) = states.get(&location) {
+                writeln!(&mut res, "must writes: {:?}", must_writes_at(states))?;
                 for state in states.values() {
                     writeln!(&mut res, "{:?}", state)?;
                 }
@@ -1030,14 +1802,131 @@ fn analysis_result_to_string(
     Ok(res)
 }
 
-fn get_param_tys<'tcx>(body: &Body<'tcx>, inputs: usize, tcx: TyCtxt<'tcx>) -> Vec<TypeInfo> {
+/// Paths written on every path reaching a location, given the states at that location.
+pub fn must_writes_at(states: &BTreeMap<(MustPathSet, MustPathSet), AbsState>) -> MustPathSet {
+    states
+        .values()
+        .fold(MustPathSet::All, |writes, st| writes.join(&st.writes))
+}
+
+fn is_zero_or_one(v: &AbsValue, ty: Ty<'_>) -> bool {
+    match ty.kind() {
+        TyKind::Int(_) => v
+            .intv
+            .gamma()
+            .is_some_and(|s| !s.is_empty() && s.iter().all(|n| *n == 0 || *n == 1)),
+        TyKind::Uint(_) => v
+            .uintv
+            .gamma()
+            .is_some_and(|s| !s.is_empty() && s.iter().all(|n| *n == 0 || *n == 1)),
+        _ => false,
+    }
+}
+
+fn constant_of(v: &AbsValue, ty: Ty<'_>) -> Option<i128> {
+    match ty.kind() {
+        TyKind::Int(_) => {
+            let s = v.intv.gamma()?;
+            (s.len() == 1).then(|| *s.first().unwrap())
+        }
+        TyKind::Uint(_) => {
+            let s = v.uintv.gamma()?;
+            (s.len() == 1).then(|| i128::try_from(*s.first().unwrap()).ok())?
+        }
+        TyKind::Bool => {
+            let s = v.boolv.gamma();
+            (s.len() == 1).then(|| s[0] as i128)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the type a param of type `ty` points to, if it can be an output param.
+/// Besides raw pointers, `NonNull<T>`, `Option<*mut T>`, and `Option<NonNull<T>>` are pointer
+/// params.
+pub fn pointee_ty<'tcx>(ty: Ty<'tcx>, mut_refs: bool, tcx: TyCtxt<'tcx>) -> Option<Ty<'tcx>> {
+    match ty.kind() {
+        TyKind::RawPtr(TypeAndMut { ty, .. }) => Some(*ty),
+        TyKind::Ref(_, ty, Mutability::Mut) if mut_refs => Some(*ty),
+        TyKind::Adt(adt_def, args) if is_non_null(adt_def.did(), tcx) => Some(args.type_at(0)),
+        TyKind::Adt(adt_def, args) if tcx.is_diagnostic_item(sym::Option, adt_def.did()) => {
+            let ty = args.type_at(0);
+            match ty.kind() {
+                TyKind::RawPtr(_) | TyKind::Adt(_, _) => pointee_ty(ty, false, tcx),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// For each param of a local function, `Some(true)` if it is annotated with
+/// `#[nopcrat::output_param]`, `Some(false)` if with `#[nopcrat::not_output_param]`, and `None`
+/// otherwise. An output param is reported even if pointer comparisons or arithmetic exclude it
+/// or some paths write only some of its fields, but not if it is read before being written.
+/// Whether it is a must param is still up to the analysis. The crate must register the tool with
+/// `#![register_tool(nopcrat)]`.
+fn param_annotations(def_id: DefId, tcx: TyCtxt<'_>) -> Vec<Option<bool>> {
+    let hir = tcx.hir();
+    let body = hir.body(hir.body_owned_by(def_id.expect_local()));
+    let is_annotated = |attrs: &[Attribute], name: &str| {
+        attrs.iter().any(|attr| {
+            let AttrKind::Normal(normal) = &attr.kind else {
+                return false;
+            };
+            let segs: Vec<_> = normal
+                .item
+                .path
+                .segments
+                .iter()
+                .map(|seg| seg.ident.as_str())
+                .collect();
+            segs == ["nopcrat", name]
+        })
+    };
+    body.params
+        .iter()
+        .map(|param| {
+            let attrs = hir.attrs(param.hir_id);
+            if is_annotated(attrs, "not_output_param") {
+                Some(false)
+            } else if is_annotated(attrs, "output_param") {
+                Some(true)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn is_non_null(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    tcx.crate_name(def_id.krate) == sym::core && tcx.item_name(def_id).as_str() == "NonNull"
+}
+
+/// The initial value of a pointer param of type `ty` pointing to `ptr`.
+fn param_value(ty: Ty<'_>, ptr: AbsValue, tcx: TyCtxt<'_>) -> AbsValue {
+    match ty.kind() {
+        TyKind::Adt(adt_def, _) if is_non_null(adt_def.did(), tcx) => {
+            AbsValue::alpha_list(vec![ptr])
+        }
+        TyKind::Adt(_, args) => AbsValue::some(param_value(args.type_at(0), ptr, tcx)),
+        _ => ptr,
+    }
+}
+
+fn get_param_tys<'tcx>(
+    body: &Body<'tcx>,
+    inputs: usize,
+    mut_refs: bool,
+    tcx: TyCtxt<'tcx>,
+) -> Vec<TypeInfo> {
     let mut param_tys = vec![];
     for (i, local) in body.local_decls.iter().enumerate() {
         if i > inputs {
             break;
         }
-        let ty = if let TyKind::RawPtr(tm) = local.ty.kind() {
-            TypeInfo::from_ty(&tm.ty, tcx)
+        let ty = if let Some(ty) = pointee_ty(local.ty, mut_refs, tcx) {
+            TypeInfo::from_ty(&ty, tcx)
         } else {
             TypeInfo::NonStruct
         };
@@ -1046,20 +1935,28 @@ fn get_param_tys<'tcx>(body: &Body<'tcx>, inputs: usize, tcx: TyCtxt<'tcx>) -> V
     param_tys
 }
 
-fn return_location(body: &Body<'_>) -> Option<Location> {
-    for block in body.basic_blocks.indices() {
-        let bbd = &body.basic_blocks[block];
-        if let Some(terminator) = &bbd.terminator {
+fn is_abi_sensitive(def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+    tcx.fn_sig(def_id).skip_binder().abi() != Abi::Rust
+        || tcx.codegen_fn_attrs(def_id).contains_extern_indicator()
+}
+
+/// Optimized MIR may have several `Return` terminators, e.g., when return blocks are
+/// duplicated.
+fn return_locations(body: &Body<'_>) -> Vec<Location> {
+    body.basic_blocks
+        .iter_enumerated()
+        .filter_map(|(block, bbd)| {
+            let terminator = bbd.terminator.as_ref()?;
             if terminator.kind == TerminatorKind::Return {
-                let location = Location {
+                Some(Location {
                     block,
                     statement_index: bbd.statements.len(),
-                };
-                return Some(location);
+                })
+            } else {
+                None
             }
-        }
-    }
-    None
+        })
+        .collect()
 }
 
 fn get_rpo_map(body: &Body<'_>) -> BTreeMap<BasicBlock, usize> {
@@ -1076,9 +1973,11 @@ fn get_loop_blocks(
     rpo_map: &BTreeMap<BasicBlock, usize>,
 ) -> BTreeMap<BasicBlock, BTreeSet<BasicBlock>> {
     let dominators = body.basic_blocks.dominators();
+    let reachable = rustc_middle::mir::traversal::reachable_as_bitset(body);
     let loop_heads: BTreeSet<_> = body
         .basic_blocks
         .indices()
+        .filter(|bb| reachable.contains(*bb))
         .flat_map(|bb| {
             let mut doms: Vec<_> = dominators.dominators(bb).collect();
             let succs: BTreeSet<_> = body.basic_blocks.successors(bb).collect();
@@ -1211,16 +2110,16 @@ fn body_size(body: &Body<'_>) -> usize {
 #[allow(unused)]
 fn show_body(body: &Body<'_>, stmt: bool, term: bool) {
     for bb in body.basic_blocks.indices() {
-        println!("{:?}", bb);
+        tracing::trace!("{:?}", bb);
         let bbd = &body.basic_blocks[bb];
         if stmt {
             for stmt in &bbd.statements {
-                println!("{:?}", stmt);
+                tracing::trace!("{:?}", stmt);
             }
         }
         if term {
             if let Some(term) = &bbd.terminator {
-                println!("{:?}", term.kind);
+                tracing::trace!("{:?}", term.kind);
             }
         }
     }
@@ -750,6 +750,19 @@ impl AbsValue {
         Self::new(self.0.subst(map))
     }
 
+    /// The pointer in this value, looking through `Some` and single-field structs such as
+    /// `NonNull`.
+    pub fn wrapped_ptr(&self) -> &AbsPtr {
+        if !self.ptrv.is_bot() {
+            return &self.ptrv;
+        }
+        match (&self.optionv, &self.listv) {
+            (AbsOption::Some(v), _) => v.wrapped_ptr(),
+            (_, AbsList::List(l)) if l.len() == 1 => l[0].wrapped_ptr(),
+            _ => &self.ptrv,
+        }
+    }
+
     pub fn make_mut(this: &mut Self) -> &mut AbsVal {
         Arc::make_mut(&mut this.0)
     }
@@ -1396,6 +1409,7 @@ impl AbsVal {
         Self {
             ptrv: self.ptrv.subst(map),
             listv: self.listv.subst(map),
+            optionv: self.optionv.subst(map),
             ..self.clone()
         }
     }
@@ -2603,6 +2617,8 @@ impl std::fmt::Debug for AbsBase {
 pub enum AbsProjElem {
     Field(usize),
     Index(AbsUint),
+    /// The value inside `Some`.
+    Payload,
 }
 
 impl std::fmt::Debug for AbsProjElem {
@@ -2610,6 +2626,7 @@ impl std::fmt::Debug for AbsProjElem {
         match self {
             Self::Field(i) => write!(f, ".{}", i),
             Self::Index(i) => write!(f, "[{:?}]", i),
+            Self::Payload => write!(f, ".some"),
         }
     }
 }
@@ -2867,6 +2884,13 @@ impl AbsOption {
     pub fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
+
+    fn subst(&self, map: &BTreeMap<usize, AbsPtr>) -> Self {
+        match self {
+            Self::Some(v) => Self::Some(v.subst(map)),
+            _ => self.clone(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -3008,7 +3032,7 @@ impl AbsPath {
         for proj in place.projection.iter() {
             match proj {
                 AbsProjElem::Field(idx) => projections.push(*idx),
-                AbsProjElem::Index(_) => {
+                AbsProjElem::Index(_) | AbsProjElem::Payload => {
                     array_access = true;
                     break;
                 }
@@ -105,8 +105,12 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
             TerminatorKind::UnwindTerminate(_) => TransferedTerminator::empty(),
             TerminatorKind::Return => TransferedTerminator::empty(),
             TerminatorKind::Unreachable => TransferedTerminator::empty(),
-            TerminatorKind::Drop { target, .. } => {
-                TransferedTerminator::state_location(state.clone(), target.start_location())
+            TerminatorKind::Drop { place, target, .. } => {
+                let (v, mut reads) = self.transfer_place(place, state);
+                reads.extend(self.get_read_paths_of_value(&v));
+                let mut new_state = state.clone();
+                new_state.add_reads(reads.into_iter());
+                TransferedTerminator::state_location(new_state, target.start_location())
             }
             TerminatorKind::Call {
                 func,
@@ -150,12 +154,13 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                     let (mut new_state, writes) = self.assign(destination, AbsValue::top(), state);
                     let reads2 = args
                         .iter()
-                        .flat_map(|arg| self.get_read_paths_of_ptr(&arg.ptrv, &[]));
+                        .flat_map(|arg| self.get_read_paths_of_ptr(arg.wrapped_ptr(), &[]));
                     reads.extend(reads2);
                     new_state.add_reads(reads.into_iter());
                     let writes = new_state.add_writes(writes.into_iter());
                     for arg in &args {
-                        self.indirect_assign(&arg.ptrv, &AbsValue::top(), &[], &mut new_state);
+                        let ptr = arg.wrapped_ptr();
+                        self.indirect_assign(ptr, &AbsValue::top(), &[], &mut new_state);
                     }
                     (vec![new_state], writes)
                 };
@@ -205,7 +210,7 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
             let v = if let Some(summary) = self.summaries.get(&callee) {
                 return self
                     .transfer_intra_call(callee, summary, args, dst, state, location, reads);
-            } else if name.contains("{extern#0}") {
+            } else if name.contains("{extern#0}") || self.is_excluded(callee) {
                 self.transfer_c_call(callee, args, &state, &mut reads)
             } else if name.contains("{impl#") {
                 self.transfer_method_call(callee, args, &mut reads)
@@ -257,8 +262,8 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
 
         let mut ptr_maps = BTreeMap::new();
         for (param, arg) in summary.init_state.local.iter().skip(1).zip(args.iter()) {
-            if let Some(idx) = param.ptrv.get_arg() {
-                ptr_maps.insert(idx, arg.ptrv.clone());
+            if let Some(idx) = param.wrapped_ptr().get_arg() {
+                ptr_maps.insert(idx, arg.wrapped_ptr().clone());
             }
         }
 
@@ -284,7 +289,7 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 .excludes
                 .iter()
                 .filter_map(|read| {
-                    let ptrs = if let AbsPtr::Set(ptrs) = &args[read.base() - 1].ptrv {
+                    let ptrs = if let AbsPtr::Set(ptrs) = args[read.base() - 1].wrapped_ptr() {
                         ptrs
                     } else {
                         return None;
@@ -301,7 +306,7 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 .reads
                 .iter()
                 .filter_map(|read| {
-                    let ptrs = if let AbsPtr::Set(ptrs) = &args[read.base() - 1].ptrv {
+                    let ptrs = if let AbsPtr::Set(ptrs) = args[read.base() - 1].wrapped_ptr() {
                         ptrs
                     } else {
                         return None;
@@ -317,7 +322,7 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
             let mut callee_writes = vec![];
             for write in return_state.writes.iter() {
                 let idx = write.base() - 1;
-                let AbsPtr::Set(ptrs) = &args[idx].ptrv else {
+                let AbsPtr::Set(ptrs) = args[idx].wrapped_ptr() else {
                     continue;
                 };
                 if ptrs.len() != 1 {
@@ -521,13 +526,16 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 }
             }
             ("ptr", "mut_ptr" | "const_ptr", _, "offset_from") => AbsValue::top_int(),
-            ("", "", "ptr", "write_volatile") => {
+            ("", "", "ptr", "write" | "write_unaligned" | "write_volatile")
+            | ("ptr", "mut_ptr", _, "write_volatile") => {
                 self.indirect_assign(&args[0].ptrv, &args[1], &[], state);
                 let writes2 = self.get_write_paths_of_ptr(&args[0].ptrv, &[]);
                 writes.extend(writes2);
                 AbsValue::top()
             }
-            ("", "", "ptr", "read_volatile") | ("", "clone", "Clone", "clone") => {
+            ("", "", "ptr", "read_volatile")
+            | ("ptr", "mut_ptr" | "const_ptr", _, "read_volatile")
+            | ("", "clone", "Clone", "clone") => {
                 let (v, reads2) = self.read_ptr(&args[0].ptrv, &[], state);
                 reads.extend(reads2);
                 v
@@ -552,6 +560,16 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                     AbsOption::Bot => AbsValue::bot(),
                 }
             }
+            ("", "", "ptr", "null" | "null_mut") => AbsValue::null(),
+            ("ptr", "non_null", _, "as_ptr") => self.get_value(&args[0], &[AbsProjElem::Field(0)]),
+            ("ptr", "non_null", _, "new_unchecked") => AbsValue::alpha_list(vec![args[0].clone()]),
+            ("ptr", "non_null", _, "new") => {
+                if args[0].ptrv.is_null() {
+                    AbsValue::none()
+                } else {
+                    AbsValue::some(AbsValue::alpha_list(vec![args[0].clone()]))
+                }
+            }
             ("", "option", _, "unwrap") => match &args[0].optionv {
                 AbsOption::Top => AbsValue::top(),
                 AbsOption::Some(v) => v.clone(),
@@ -595,8 +613,17 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 reads.extend(reads2);
                 let writes2 = self.get_write_paths_of_ptr(&args[0].ptrv, &[]);
                 writes.extend(writes2);
+                self.indirect_assign(&args[0].ptrv, &AbsValue::top(), &[], state);
                 args[0].clone()
             }
+            ("", "", "intrinsics", "copy_nonoverlapping" | "copy") => {
+                let reads2 = self.get_read_paths_of_ptr(&args[0].ptrv, &[]);
+                reads.extend(reads2);
+                let writes2 = self.get_write_paths_of_ptr(&args[1].ptrv, &[]);
+                writes.extend(writes2);
+                self.indirect_assign(&args[1].ptrv, &AbsValue::top(), &[], state);
+                AbsValue::top()
+            }
             ("", "vec", _, "as_mut_ptr") => AbsValue::top_ptr(),
             (_, "ffi", _, "arg" | "as_va_list")
             | ("", "", "AsmCastTrait", "cast_in")
@@ -626,6 +653,21 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 reads.extend(reads0);
                 AbsValue::top()
             }
+            ("ops", "function", "Fn" | "FnMut" | "FnOnce", "call" | "call_mut" | "call_once") => {
+                // the closure body is not analyzed, so its captures may be read and written
+                let closure = if args[0].ptrv.gamma().is_some() {
+                    self.read_ptr(&args[0].ptrv, &[], state).0
+                } else {
+                    args[0].clone()
+                };
+                for v in [&closure, &args[1]] {
+                    for ptr in self.get_ptrs_of_value(v) {
+                        reads.extend(self.get_read_paths_of_ptr(&ptr, &[]));
+                        self.indirect_assign(&ptr, &AbsValue::top(), &[], state);
+                    }
+                }
+                AbsValue::top()
+            }
             ("", "cmp", "PartialOrd", "lt" | "le" | "gt" | "ge")
             | ("", "cmp", "PartialEq", "eq" | "ne") => {
                 let reads0 = self.get_read_paths_of_ptr(&args[0].ptrv, &[]);
@@ -822,9 +864,13 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 };
                 (v, reads, vec![])
             }
-            Rvalue::Discriminant(_) => todo!("{:?}", rvalue),
+            Rvalue::Discriminant(place) => {
+                // only `Option`s are matched, and `SwitchInt` on an integer takes every target
+                let (_, reads) = self.transfer_place(place, state);
+                (AbsValue::top_int(), reads, vec![])
+            }
             Rvalue::Aggregate(box kind, fields) => match kind {
-                AggregateKind::Array(_) => {
+                AggregateKind::Array(_) | AggregateKind::Tuple | AggregateKind::Closure(_, _) => {
                     let (vs, readss): (Vec<_>, Vec<_>) = fields
                         .iter()
                         .map(|operand| self.transfer_operand(operand, state))
@@ -833,7 +879,6 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                     let reads = readss.into_iter().flatten().collect();
                     (v, reads, vec![])
                 }
-                AggregateKind::Tuple => unreachable!("{:?}", rvalue),
                 AggregateKind::Adt(def_id, _, _, _, _) => {
                     let adt_def = self.tcx.adt_def(def_id);
                     match adt_def.adt_kind() {
@@ -876,7 +921,6 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                         }
                     }
                 }
-                AggregateKind::Closure(_, _) => unreachable!("{:?}", rvalue),
                 AggregateKind::Generator(_, _, _) => unreachable!("{:?}", rvalue),
             },
             Rvalue::ShallowInitBox(_, _) => unreachable!("{:?}", rvalue),
@@ -975,8 +1019,11 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
             ConstValue::ZeroSized => {
                 if let TyKind::FnDef(def_id, _) = ty.kind() {
                     AbsValue::alpha_fn(*def_id)
+                } else if ty.is_unit() || ty.is_closure() {
+                    AbsValue::alpha_list(vec![])
                 } else {
-                    unreachable!("{:?}", v)
+                    // e.g., `PhantomData` and unit structs
+                    self.top_value_of_ty(ty)
                 }
             }
             ConstValue::Slice { data, start, end } => {
@@ -1071,10 +1118,25 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
         projection: &[PlaceElem<'tcx>],
         state: &AbsState,
     ) -> Vec<AbsProjElem> {
-        projection
-            .iter()
-            .map(|e| self.abstract_elem(e, state))
-            .collect()
+        let mut elems = vec![];
+        let mut downcast = false;
+        for elem in projection {
+            if downcast {
+                // the payload of `Some` is its only field
+                assert!(
+                    matches!(elem, ProjectionElem::Field(f, _) if f.index() == 0),
+                    "{:?}",
+                    projection
+                );
+                downcast = false;
+            } else if let ProjectionElem::Downcast(_, _) = elem {
+                elems.push(AbsProjElem::Payload);
+                downcast = true;
+            } else {
+                elems.push(self.abstract_elem(elem, state));
+            }
+        }
+        elems
     }
 
     fn abstract_elem(&self, elem: &PlaceElem<'tcx>, state: &AbsState) -> AbsProjElem {
@@ -1180,6 +1242,11 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                         }
                     }
                 }
+                AbsProjElem::Payload => {
+                    if let AbsOption::Some(old_v) = &mut AbsValue::make_mut(old_v).optionv {
+                        self.update_value(new_v, old_v, weak, &projection[1..]);
+                    }
+                }
             }
         } else if weak {
             *old_v = new_v.join(old_v);
@@ -1225,6 +1292,27 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
         }
     }
 
+    /// The destructor of a value may read through any pointer it contains.
+    fn get_read_paths_of_value(&self, v: &AbsValue) -> Vec<AbsPath> {
+        self.get_ptrs_of_value(v)
+            .iter()
+            .flat_map(|ptr| self.get_read_paths_of_ptr(ptr, &[]))
+            .collect()
+    }
+
+    fn get_ptrs_of_value(&self, v: &AbsValue) -> Vec<AbsPtr> {
+        let mut ptrs = vec![v.ptrv.clone()];
+        if let AbsList::List(l) = &v.listv {
+            for v in l {
+                ptrs.extend(self.get_ptrs_of_value(v));
+            }
+        }
+        if let AbsOption::Some(v) = &v.optionv {
+            ptrs.extend(self.get_ptrs_of_value(v));
+        }
+        ptrs
+    }
+
     fn get_value(&self, v: &AbsValue, projection: &[AbsProjElem]) -> AbsValue {
         let first = some_or!(projection.first(), return v.clone());
         match first {
@@ -1263,6 +1351,11 @@ impl<'tcx> super::analysis::Analyzer<'_, 'tcx> {
                 }
                 AbsList::Bot => AbsValue::bot(),
             },
+            AbsProjElem::Payload => match &v.optionv {
+                AbsOption::Top => AbsValue::top(),
+                AbsOption::Some(v) => self.get_value(v, &projection[1..]),
+                _ => AbsValue::bot(),
+            },
         }
     }
 }
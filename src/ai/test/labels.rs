@@ -493,6 +493,38 @@ fn test_volatile_write() {
     assert_eq!(result[0].reads.len(), 0);
 }
 
+#[test]
+fn test_ptr_write() {
+    let code = "
+        unsafe fn f(b: bool, p: *mut i32) -> i32 {
+            std::ptr::write(p, if b { 0 } else { 1 });
+            *p
+        }
+    ";
+    let result = analyze(code);
+    assert_eq!(result.len(), 1);
+
+    assert_eq!(result[0].writes.len(), 1);
+    assert_eq!(result[0].writes.as_vec()[0].0, vec![2]);
+    assert_eq!(result[0].reads.len(), 0);
+}
+
+#[test]
+fn test_copy_nonoverlapping() {
+    let code = "
+        unsafe fn f(p: *mut i32, q: *const i32) {
+            std::ptr::copy_nonoverlapping(q, p, 1);
+        }
+    ";
+    let result = analyze(code);
+    assert_eq!(result.len(), 1);
+
+    assert_eq!(result[0].writes.len(), 1);
+    assert_eq!(result[0].writes.as_vec()[0].0, vec![1]);
+    assert_eq!(result[0].reads.len(), 1);
+    assert_eq!(result[0].reads.as_vec()[0].0, vec![2]);
+}
+
 #[test]
 fn test_bitfields() {
     let code = "
@@ -11,6 +11,7 @@ mod float;
 mod fnptr;
 mod int;
 mod labels;
+mod params;
 mod ptr;
 mod structs;
 mod uint;
@@ -62,6 +63,7 @@ fn as_uint(v: &AbsVal) -> Vec<u128> {
     v.uintv.gamma().unwrap().iter().cloned().collect()
 }
 
+#[allow(clippy::iter_cloned_collect)]
 fn as_float(v: &AbsVal) -> Vec<f64> {
     assert!(v.intv.is_bot());
     assert!(v.uintv.is_bot());
@@ -106,6 +108,7 @@ fn is_none(v: &AbsVal) {
     assert!(v.fnv.is_bot());
 }
 
+#[allow(clippy::needless_borrow)]
 fn as_some(v: &AbsVal) -> &AbsVal {
     assert!(v.intv.is_bot());
     assert!(v.uintv.is_bot());
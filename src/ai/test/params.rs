@@ -0,0 +1,918 @@
+use super::*;
+
+fn analyze_params(code: &str) -> analysis::AnalysisResult {
+    analysis::analyze_code(code, &analysis::AnalysisConfig::default()).unwrap()
+}
+
+#[test]
+fn test_location() {
+    let code = "
+        unsafe fn f(n: i32, p: *mut i32) {
+            *p = n;
+        }
+    ";
+    let result = analyze_params(code);
+    let location = &result["f"][0].location;
+    assert_eq!(location.lo, 32);
+    assert_eq!(location.hi, 40);
+    assert_eq!(location.line, 2);
+    assert_eq!(location.column, 32);
+    assert_eq!(location.file, compile_util::CODE_FILE_NAME);
+
+    let stats = analysis::stats_code(code, &analysis::AnalysisConfig::default()).unwrap();
+    let files: Vec<_> = stats.files.keys().collect();
+    assert_eq!(
+        files,
+        vec![std::path::Path::new(compile_util::CODE_FILE_NAME)]
+    );
+}
+
+#[test]
+fn test_name() {
+    let code = "
+        unsafe fn f(n: i32, out_len: *mut i32, (a, _): (i32, i32), _: *mut i32) {
+            *out_len = n + a;
+        }
+        unsafe fn g(_: *mut i32, p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result["f"].len(), 1);
+    assert_eq!(result["f"][0].name.as_deref(), Some("out_len"));
+    assert_eq!(result["g"].len(), 1);
+    assert_eq!(result["g"][0].name.as_deref(), Some("p"));
+}
+
+#[test]
+fn test_min_output_params() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32, q: *mut i32) {
+            *p = 0;
+            *q = 0;
+        }
+    ";
+    let conf = analysis::AnalysisConfig {
+        min_output_params: 2,
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["g"]);
+}
+
+#[test]
+fn test_function_filter() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let conf = analysis::AnalysisConfig {
+        function_filter: Some(regex::Regex::new("^g$").unwrap()),
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["g"]);
+}
+
+#[test]
+fn test_file_filter() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let conf = analysis::AnalysisConfig {
+        file_filter: Some(glob::Pattern::new("*.rs").unwrap()),
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert_eq!(result.len(), 1);
+
+    let conf = analysis::AnalysisConfig {
+        file_filter: Some(glob::Pattern::new("ffi/*.rs").unwrap()),
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert!(result.is_empty());
+}
+
+#[test]
+fn test_copy_for_deref_read() {
+    let code = "
+        unsafe fn f(p: *mut *mut i32) -> i32 {
+            let x = **p;
+            *p = 0 as *mut i32;
+            x
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(result.is_empty());
+}
+
+#[test]
+fn test_inherent_method() {
+    let code = "
+        struct S;
+        impl S {
+            unsafe fn f(&self, p: *mut i32) {
+                *p = 0;
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["S::f"]);
+    assert_eq!(result["S::f"][0].index, 1);
+}
+
+#[test]
+fn test_trait_method() {
+    let code = "
+        struct S;
+        unsafe trait T {
+            unsafe fn f(&self, p: *mut i32);
+        }
+        unsafe impl T for S {
+            unsafe fn f(&self, p: *mut i32) {
+                *p = 0;
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["<S as T>::f"]);
+}
+
+#[test]
+fn test_location_map() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+
+        unsafe fn g(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let input = compile_util::str_to_input(code);
+    let map = analysis::location_map_input(input, &analysis::AnalysisConfig::default()).unwrap();
+    let keys: Vec<_> = map.keys().map(|(_, line)| *line).collect();
+    assert_eq!(keys, vec![2, 6]);
+    assert!(map[&(std::path::PathBuf::from(compile_util::CODE_FILE_NAME), 6)].contains_key("g"));
+}
+
+#[test]
+fn test_copy_nonoverlapping() {
+    let code = "
+        unsafe fn f(p: *mut i32, q: *const i32) {
+            std::ptr::copy_nonoverlapping(q, p, 1);
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result["f"].len(), 1);
+    assert_eq!(result["f"][0].index, 0);
+    assert!(result["f"][0].must);
+}
+
+#[test]
+fn test_copy_after_const() {
+    let code = "
+        unsafe fn f(p: *mut i32, q: *const i32) {
+            *p = 0;
+            std::ptr::copy_nonoverlapping(q, p, 1);
+        }
+        unsafe fn g(p: *mut i32, q: *const i32) {
+            *p = 0;
+            std::ptr::copy(q, p, 1);
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(result["f"][0].must);
+    assert_eq!(result["f"][0].const_write, None);
+    assert!(result["g"][0].must);
+    assert_eq!(result["g"][0].const_write, None);
+}
+
+#[test]
+fn test_union() {
+    let code = "
+        union U { x: i32, y: f32 }
+        unsafe fn f(p: *mut U) {
+            *p = U { x: 0 };
+        }
+        unsafe fn g(p: *mut U) {
+            (*p).x = 0;
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(result.is_empty());
+}
+
+#[test]
+fn test_compile_error() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = true;
+        }
+    ";
+    let conf = analysis::AnalysisConfig::default();
+    let error = analysis::analyze_code(code, &conf).unwrap_err();
+    assert_eq!(error.errors.len(), 1);
+    assert_eq!(error.errors[0], "<main.rs>:3:18: 3:22: mismatched types");
+    assert!(error.to_string().contains("mismatched types"));
+}
+
+#[test]
+fn test_mut_ref() {
+    let code = "
+        fn f(n: i32, p: &mut i32) {
+            *p = n;
+        }
+        fn g(n: i32, p: &mut i32) -> i32 {
+            if n < 0 {
+                return -1;
+            }
+            *p = n;
+            0
+        }
+        fn h(n: i32, p: &mut i32) {
+            *p += n;
+        }
+        fn k(p: &mut i32) {
+            f(0, p);
+        }
+    ";
+    assert!(analyze_params(code).is_empty());
+
+    let conf = analysis::AnalysisConfig {
+        mut_refs: true,
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["f", "g", "k"]);
+    assert!(result["f"][0].must);
+    assert_eq!(result["f"][0].index, 1);
+    assert!(!result["g"][0].must);
+    assert!(result["k"][0].must);
+}
+
+#[test]
+fn test_null_guarded() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            if p.is_null() {
+                return;
+            }
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn h(n: i32, p: *mut i32) {
+            if n < 0 {
+                return;
+            }
+            *p = 0;
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(result["f"][0].must);
+    assert!(result["f"][0].null_guarded);
+    assert!(result["g"][0].must);
+    assert!(!result["g"][0].null_guarded);
+    assert!(!result["h"][0].must);
+    assert!(!result["h"][0].null_guarded);
+}
+
+#[test]
+fn test_stats() {
+    let code = "
+        unsafe fn f(p: *mut i32, q: *mut i32) {
+            *p = 0;
+            *q = 0;
+        }
+        unsafe fn g(b: bool, p: *mut i32) {
+            if b {
+                *p = 0;
+            }
+        }
+        unsafe fn h(p: *mut i32) -> i32 {
+            *p
+        }
+        unsafe fn k(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn skip_me(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn main() {
+            let _ = k as unsafe fn(*mut i32);
+        }
+    ";
+    let conf = analysis::AnalysisConfig {
+        function_filter: Some(regex::Regex::new("^[a-z]$").unwrap()),
+        ..analysis::AnalysisConfig::default()
+    };
+    let stats = analysis::stats_code(code, &conf).unwrap();
+    let total = analysis::Counts {
+        functions: 5,
+        with_output_params: 2,
+        musts: 2,
+        mays: 1,
+    };
+    assert_eq!(stats.total, total);
+    assert_eq!(stats.skipped["address taken"], 1);
+    assert_eq!(stats.skipped["filtered out"], 1);
+    assert_eq!(stats.files.len(), 1);
+    assert_eq!(stats.files.values().next(), Some(&total));
+    assert!(stats.analysis_times.is_empty());
+
+    let conf = analysis::AnalysisConfig {
+        function_times: Some(4),
+        ..conf
+    };
+    let stats = analysis::stats_code(code, &conf).unwrap();
+    assert_eq!(stats.analysis_times.len(), 4);
+    assert!(stats.analysis_times.windows(2).all(|w| w[0].1 >= w[1].1));
+}
+
+#[test]
+fn test_analysis_time() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    assert_eq!(analyze_params(code)["f"][0].analysis_time_ms, None);
+    let conf = analysis::AnalysisConfig {
+        function_times: Some(0),
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert!(result["f"][0].analysis_time_ms.is_some());
+}
+
+#[test]
+fn test_stats_address_taken() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) -> i32 {
+            *p
+        }
+        unsafe fn h() {
+            let _ = f as unsafe fn(*mut i32);
+            let _ = g as unsafe fn(*mut i32) -> i32;
+        }
+    ";
+    let stats = analysis::stats_code(code, &analysis::AnalysisConfig::default()).unwrap();
+    assert_eq!(stats.skipped.len(), 1);
+    assert_eq!(stats.skipped["address taken"], 1);
+    assert_eq!(stats.total.functions, 3);
+    assert_eq!(stats.total.with_output_params, 0);
+    assert!(analyze_params(code).is_empty());
+}
+
+#[test]
+fn test_two_returns() {
+    let code = "
+        unsafe fn f(n: i32, p: *mut i32) -> i32 {
+            if n < 0 {
+                return -1;
+            }
+            *p = n;
+            return 0;
+        }
+        unsafe fn g(n: i32, p: *mut i32) -> i32 {
+            if n < 0 {
+                *p = 0;
+                return -1;
+            }
+            *p = n;
+            return 0;
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(!result["f"][0].must);
+    assert!(result["g"][0].must);
+}
+
+#[test]
+fn test_display() {
+    let code = "
+        unsafe fn f(p: *mut i32, q: *mut i32) {
+            *p = 0;
+            if q.is_null() {
+                return;
+            }
+            *q = 0;
+        }
+        unsafe fn g(b: bool, p: *mut i32) {
+            if b {
+                *p = 0;
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(
+        analysis::DisplayResult(&result).to_string(),
+        "fn f: output params [param 0 `p` (must-write), param 1 `q` (must-write, null-guarded)]\n\
+         fn g: output params [param 1 `p` (may-write)]\n"
+    );
+}
+
+#[test]
+fn test_tests_excluded() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        #[cfg(test)]
+        mod tests {
+            pub unsafe fn g(p: *mut i32) {
+                *p = 0;
+            }
+            #[test]
+            fn t() {
+                let mut x = 0;
+                unsafe { g(&mut x) };
+            }
+        }
+    ";
+    assert_eq!(analyze_params(code).keys().collect::<Vec<_>>(), vec!["f"]);
+
+    let conf = analysis::AnalysisConfig {
+        include_tests: true,
+        ..analysis::AnalysisConfig::default()
+    };
+    let result = analysis::analyze_code(code, &conf).unwrap();
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["f", "tests::g"]);
+}
+
+#[test]
+fn test_const_write() {
+    let code = "
+        unsafe fn f(b: bool, p: *mut i32, q: *mut i32, r: *mut bool) {
+            if b {
+                *p = 0;
+                *q = 1;
+            } else {
+                *p = 0;
+                *q = 2;
+            }
+            *r = true;
+        }
+        unsafe fn g(b: bool, p: *mut i32) {
+            if b {
+                *p = 0;
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    let consts: Vec<_> = result["f"].iter().map(|p| p.const_write).collect();
+    assert_eq!(consts, vec![Some(0), None, Some(1)]);
+    assert_eq!(result["g"][0].const_write, None);
+}
+
+#[test]
+fn test_drop_read() {
+    let code = "
+        struct S(*mut i32);
+        impl Drop for S {
+            fn drop(&mut self) {
+                let _x = unsafe { *self.0 };
+            }
+        }
+        unsafe fn f(p: *mut i32) {
+            {
+                let _s = S(p);
+            }
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) {
+            *p = 0;
+            {
+                let _s = S(p);
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["g"]);
+}
+
+#[test]
+fn test_closure() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            let c = || *p = 0;
+            c();
+        }
+        unsafe fn g(p: *mut i32) {
+            let c = move |x: i32| *p = x;
+            c(1);
+            *p = 2;
+        }
+        unsafe fn h(p: *mut i32) {
+            let mut x = 0;
+            let mut c = || x = 1;
+            c();
+            *p = x;
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["h"]);
+    assert!(result["h"][0].must);
+    assert_eq!(result["h"][0].const_write, None);
+}
+
+#[test]
+fn test_diverging_path() {
+    let code = "
+        unsafe fn f(b: bool, p: *mut i32) {
+            if b {
+                panic!();
+            }
+            *p = 0;
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(result["f"][0].must);
+}
+
+#[test]
+fn test_ptr_wrappers() {
+    let code = "
+        use std::ptr::NonNull;
+        unsafe fn f(p: NonNull<i32>) {
+            *p.as_ptr() = 0;
+        }
+        unsafe fn g(p: Option<*mut i32>) {
+            if let Some(p) = p {
+                *p = 0;
+            }
+        }
+        unsafe fn h(p: Option<NonNull<i32>>) {
+            match p {
+                Some(p) => *p.as_ptr() = 0,
+                None => {}
+            }
+        }
+        unsafe fn k(p: Option<*mut i32>) -> i32 {
+            let Some(p) = p else {
+                return -1;
+            };
+            *p = 0;
+            0
+        }
+        unsafe fn m(p: NonNull<i32>) {
+            f(p);
+        }
+        unsafe fn n(p: NonNull<i32>) -> i32 {
+            *p.as_ptr()
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(
+        result.keys().collect::<Vec<_>>(),
+        vec!["f", "g", "h", "k", "m"]
+    );
+    assert!(result["f"][0].must);
+    assert!(!result["g"][0].must);
+    assert!(!result["h"][0].must);
+    assert!(!result["k"][0].must);
+    assert!(result["m"][0].must);
+}
+
+#[test]
+fn test_read_in_call() {
+    let code = "
+        extern \"C\" {
+            fn h(p: *const i32) -> i32;
+        }
+        unsafe fn g(p: *mut i32) -> i32 {
+            *p + 1
+        }
+        unsafe fn f(p: *mut i32, q: *mut i32) -> i32 {
+            let n = g(p) + h(q);
+            *p = 0;
+            *q = 0;
+            n
+        }
+    ";
+    let result = analyze_params(code);
+    assert!(!result.contains_key("f"));
+}
+
+#[test]
+fn test_call_graph() {
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) {
+            *p = 1;
+        }
+        unsafe fn h() -> i32 {
+            let mut x = 0;
+            f(&mut x);
+            let c = || f(&mut x);
+            c();
+            x
+        }
+        unsafe fn i() -> i32 {
+            let mut x = 0;
+            f(&mut x);
+            h() + x
+        }
+    ";
+    let conf = analysis::AnalysisConfig::default();
+    let result = analyze_params(code);
+    let graph = analysis::call_graph_code(code, &result, &conf).unwrap();
+    assert_eq!(graph.edges.len(), 2);
+    assert_eq!(graph.edges["f"], vec!["h", "i"]);
+    assert!(graph.edges["g"].is_empty());
+    assert_eq!(
+        graph.to_dot(),
+        "digraph {\n    \"f\";\n    \"h\" -> \"f\";\n    \"i\" -> \"f\";\n    \"g\";\n}\n"
+    );
+}
+
+#[test]
+fn test_call_graph_array_len() {
+    let code = "
+        const fn n() -> usize {
+            4
+        }
+        pub struct S {
+            a: [u8; n()],
+        }
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g() -> [u8; n()] {
+            let mut x = 0;
+            f(&mut x);
+            [0; n()]
+        }
+    ";
+    let conf = analysis::AnalysisConfig::default();
+    let result = analyze_params(code);
+    let graph = analysis::call_graph_code(code, &result, &conf).unwrap();
+    assert_eq!(graph.edges["f"], vec!["g"]);
+}
+
+#[test]
+fn test_convertibility_score() {
+    let code = "
+        unsafe fn f(b: bool, p: *mut i32) {
+            if b {
+                *p = 0;
+            }
+        }
+        unsafe fn g(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn h(b: bool, p: *mut i32) -> i32 {
+            if b {
+                *p = 0;
+                0
+            } else {
+                -1
+            }
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(analysis::convertibility_score(&result["f"]), 0);
+    assert_eq!(analysis::convertibility_score(&result["g"]), 2);
+    assert_eq!(analysis::convertibility_score(&result["h"]), 1);
+    assert_eq!(
+        analysis::DisplayRanked(&result).to_string(),
+        "fn g (score 2): output params [param 0 `p` (must-write)]\n\
+         fn h (score 1): output params [param 1 `p` (may-write)]\n\
+         fn f (score 0): output params [param 1 `p` (may-write)]\n"
+    );
+}
+
+#[test]
+fn test_read_after_write() {
+    let code = "
+        unsafe fn f(b: bool, p: *mut i32) -> i32 {
+            if b {
+                *p = 1;
+            } else {
+                *p = 2;
+            }
+            *p + 1
+        }
+        unsafe fn g(b: bool, p: *mut i32) -> i32 {
+            if b {
+                *p = 1;
+            }
+            *p + 1
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result.keys().collect::<Vec<_>>(), vec!["f"]);
+    assert!(result["f"][0].must);
+}
+
+#[test]
+fn test_aggregate_write() {
+    let code = "
+        pub struct T {
+            x: i32,
+        }
+        pub struct S {
+            a: i32,
+            t: T,
+        }
+        pub unsafe fn f(p: *mut S, a: i32) {
+            *p = S { a, t: T { x: 2 } };
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result["f"].len(), 1);
+    assert!(result["f"][0].must);
+    assert!(result["f"][0].fully_initialized);
+}
+
+#[test]
+fn test_annotations() {
+    let code = "
+        #![feature(register_tool)]
+        #![register_tool(nopcrat)]
+        unsafe fn f(#[nopcrat::not_output_param] p: *mut i32, q: *mut i32) {
+            *p = 0;
+            *q = 0;
+        }
+        unsafe fn g(b: bool, #[nopcrat::output_param] p: *mut i32) {
+            if b {
+                *p = 0;
+            }
+        }
+        unsafe fn h(#[nopcrat::output_param] p: *mut i32) -> i32 {
+            let x = *p;
+            *p = 0;
+            x
+        }
+    ";
+    let result = analyze_params(code);
+    assert_eq!(result["f"].len(), 1);
+    assert_eq!(result["f"][0].index, 1);
+    assert_eq!(result["g"].len(), 1);
+    assert!(!result["g"][0].must);
+    assert!(!result.contains_key("h"));
+}
+
+#[test]
+fn test_target_and_cfg() {
+    let code = "
+        #![feature(no_core, lang_items)]
+        #![no_core]
+        #[lang = \"sized\"]
+        trait Sized {}
+        #[lang = \"copy\"]
+        trait Copy {}
+        impl Copy for i32 {}
+        #[cfg(target_pointer_width = \"32\")]
+        pub unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        #[cfg(target_pointer_width = \"64\")]
+        pub unsafe fn f(p: *mut i32) {}
+        #[cfg(feature = \"g\")]
+        pub unsafe fn g(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let analyze = |target: &str, cfgs: &[&str]| {
+        let conf = analysis::AnalysisConfig {
+            compile: compile_util::CompileOptions {
+                target: Some(target.to_string()),
+                cfgs: cfgs.iter().map(|c| c.to_string()).collect(),
+                ..compile_util::CompileOptions::default()
+            },
+            ..analysis::AnalysisConfig::default()
+        };
+        let result = analysis::analyze_code(code, &conf).unwrap();
+        result.into_keys().collect::<Vec<_>>()
+    };
+    assert_eq!(analyze("i686-unknown-linux-gnu", &[]), vec!["f"]);
+    assert!(analyze("x86_64-unknown-linux-gnu", &[]).is_empty());
+    assert_eq!(
+        analyze("x86_64-unknown-linux-gnu", &["feature=\"g\""]),
+        vec!["g"]
+    );
+}
+
+#[test]
+fn test_volatile() {
+    let code = "
+        pub unsafe fn f(p: *mut i32, q: *mut i32, r: *mut i32) {
+            std::ptr::write_volatile(p, 1);
+            (q as *mut u32).offset(1).write_volatile(2);
+            *r = 3;
+        }
+    ";
+    let result = analysis::analyze_code(code, &analysis::AnalysisConfig::default()).unwrap();
+    let volatile: Vec<_> = result["f"].iter().map(|p| (p.index, p.volatile)).collect();
+    assert_eq!(volatile, vec![(0, true), (2, false)]);
+}
+
+#[test]
+fn test_size_limits() {
+    let code = "
+        pub unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        pub unsafe fn g(p: *mut i32, n: i32) {
+            let mut i = 0;
+            while i < n {
+                i += 1;
+            }
+            *p = i;
+        }
+    ";
+    let analyze = |max_file_size, max_function_size| {
+        let conf = analysis::AnalysisConfig {
+            max_file_size,
+            max_function_size,
+            ..analysis::AnalysisConfig::default()
+        };
+        let result = analysis::analyze_code(code, &conf).unwrap();
+        result.into_keys().collect::<Vec<_>>()
+    };
+    assert_eq!(analyze(None, None), vec!["f", "g"]);
+    assert_eq!(analyze(None, Some(2)), vec!["f"]);
+    assert_eq!(analyze(Some(code.len()), None), vec!["f", "g"]);
+    assert!(analyze(Some(code.len() - 1), None).is_empty());
+}
+
+#[test]
+fn test_mir() {
+    let conf = analysis::AnalysisConfig::default();
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+        unsafe fn g(p: *mut i32) {
+            f(p);
+        }
+    ";
+    let names = ["f".to_string()].into_iter().collect();
+    let mir = analysis::mir_code(code, &names, &conf).unwrap();
+    assert!(mir.contains("fn f("));
+    assert!(!mir.contains("fn g("));
+    let mir = analysis::mir_code(code, &std::collections::BTreeSet::new(), &conf).unwrap();
+    assert!(mir.contains("fn f(") && mir.contains("fn g("));
+}
+
+#[test]
+fn test_check() {
+    let conf = analysis::AnalysisConfig::default();
+    let code = "
+        unsafe fn f(p: *mut i32) {
+            *p = 0;
+        }
+    ";
+    let result = analysis::check_code(code, &conf).unwrap().unwrap();
+    assert_eq!(result["f"].len(), 1);
+    let code = "
+        unsafe fn f(p: *mut i32) -> i32 {
+            *p
+        }
+    ";
+    assert!(analysis::check_code(code, &conf).unwrap().is_none());
+}
+
+#[test]
+fn test_loop_write() {
+    let code = "
+        pub unsafe fn f(p: *mut i32, q: *mut i32, r: *mut i32, n: i32) {
+            let mut i = 0;
+            *q = 0;
+            loop {
+                *p = i;
+                *q += i;
+                i += 1;
+                if i >= n {
+                    break;
+                }
+            }
+            *r = i;
+        }
+    ";
+    let result = analyze_params(code);
+    let loop_write: Vec<_> = result["f"]
+        .iter()
+        .map(|p| (p.index, p.loop_write))
+        .collect();
+    assert_eq!(loop_write, vec![(0, true), (1, false), (2, false)]);
+    assert!(result["f"][0].must);
+}
@@ -1,13 +1,16 @@
 use std::{
     alloc::{Layout, System},
     fs::{self, File},
+    io::Write,
     path::{Path, PathBuf},
     time::Instant,
 };
 
-use clap::Parser;
+use clap::{ArgAction, Parser, ValueEnum};
+use glob::Pattern;
 use nopcrat::*;
 use rand::prelude::*;
+use regex::Regex;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -15,18 +18,71 @@ struct Args {
     dump_analysis_result: Option<PathBuf>,
     #[arg(short, long)]
     use_analysis_result: Option<PathBuf>,
+    /// Write the functions with output params and their callers to this file in DOT
+    #[arg(long)]
+    call_graph: Option<PathBuf>,
 
-    #[arg(short, long)]
-    verbose: bool,
+    /// Log diagnostics to stderr (or the log file); -v for debug, -vv for trace
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Order of the functions in the text output
+    #[arg(long, value_enum, default_value_t = SortBy::Name)]
+    sort_by: SortBy,
     #[arg(short, long)]
     max_loop_head_states: Option<usize>,
     #[arg(long)]
     no_widening: bool,
 
+    /// Only print the functions with output params, exiting with 1 if there are any
+    #[arg(long)]
+    check_only: bool,
     #[arg(short, long)]
     transform: bool,
+    /// Write what the transformation would do to this file as JSON instead of applying it
+    #[arg(long)]
+    report_only: Option<PathBuf>,
+    /// Rewrite only function signatures and bodies; call sites must be updated manually
+    #[arg(long)]
+    no_transform_calls: bool,
+    #[arg(long)]
+    allow_abi_break: bool,
+    /// Check that the transformed crate compiles, leaving functions that break it untouched
+    #[arg(long)]
+    verify: bool,
+    #[arg(long)]
+    narrow_bool_outputs: bool,
+    /// Return a may output param as `Result` only if the function returns this value on success
+    #[arg(long, allow_negative_numbers = true)]
+    errno_to_result: Option<i128>,
+    /// How locals replacing output params are initialized
+    #[arg(long, value_enum, default_value_t = Init::MaybeUninit)]
+    init_strategy: Init,
+    /// How functions return multiple values
+    #[arg(long, value_enum, default_value_t = WriteBack::Tuple)]
+    write_back_style: WriteBack,
+    /// Transform only this function (repeatable)
+    #[arg(long)]
+    only: Vec<String>,
+    /// Do not transform this function (repeatable)
+    #[arg(long)]
+    skip: Vec<String>,
+    /// Keep each function as `<name>_raw` and generate `<name>` with the new signature calling it
+    #[arg(long, conflicts_with = "keep_wrapper")]
+    generate_wrapper: bool,
+    /// Transform each function into `<name>_inner` and keep `<name>` as a wrapper calling it;
+    /// call sites are not rewritten
+    #[arg(long)]
+    keep_wrapper: bool,
+    /// Format each modified file with rustfmt; files rustfmt fails on are left as they are
+    #[arg(long)]
+    fmt: bool,
     #[arg(short, long)]
     size: bool,
+    /// Print how many functions and params are reported, in total and per file
+    #[arg(long)]
+    stats: bool,
     #[arg(long)]
     sample_negative: bool,
     #[arg(long)]
@@ -35,29 +91,104 @@ struct Args {
     sample_must: bool,
     #[arg(long)]
     time: bool,
+    /// Log the analysis time of each function, add it to the analysis result, and print the
+    /// given number of slowest functions; with --stats, list them
     #[arg(long)]
     function_times: Option<usize>,
+    #[arg(long)]
+    min_params: Option<usize>,
+    #[arg(long, value_parser = Regex::new)]
+    function_regex: Option<Regex>,
+    #[arg(long, value_parser = Pattern::new)]
+    file_glob: Option<Pattern>,
+    /// Treat `&mut T` params as output param candidates too
+    #[arg(long)]
+    mut_refs: bool,
+    /// Analyze and transform only the functions in this module, e.g., `crate::ffi`
+    #[arg(long)]
+    module_prefix: Option<String>,
+    /// Do not analyze functions in files larger than this many bytes
+    #[arg(long)]
+    max_file_size: Option<usize>,
+    /// Do not analyze functions whose MIR has more basic blocks than this
+    #[arg(long)]
+    max_function_size: Option<usize>,
+    /// Also analyze `#[test]` functions and `#[cfg(test)]` modules
+    #[arg(long)]
+    include_tests: bool,
 
-    #[arg(short, long)]
+    /// Print the abstract states at every location of this function
+    #[arg(short, long, alias = "dump-dataflow")]
     print_function: Vec<String>,
+    /// Print the optimized MIR of this function, or of every function if no name is given
+    #[arg(long, num_args = 0..=1, require_equals = true)]
+    print_mir: Option<Vec<String>>,
     #[arg(short, long)]
     log_file: Option<PathBuf>,
+    /// Sysroot used to compile the input; defaults to $NOPCRAT_SYSROOT or the current toolchain
+    #[arg(long)]
+    sysroot: Option<PathBuf>,
+    /// Edition used to compile the input
+    #[arg(long, value_parser = ["2015", "2018", "2021"], default_value = "2021")]
+    edition: String,
+    /// Target triple used to compile the input; its standard library must be in the sysroot
+    #[arg(long)]
+    target: Option<String>,
+    /// `cfg` flag used to compile the input, e.g., `feature="x"` (repeatable)
+    #[arg(long)]
+    cfg: Vec<String>,
     #[arg(short, long)]
     output: Option<PathBuf>,
     input: PathBuf,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum SortBy {
+    Name,
+    /// Functions that are easier to transform first
+    Score,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum Init {
+    Zero,
+    MaybeUninit,
+    Default,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum WriteBack {
+    Tuple,
+    Struct,
+}
+
 fn main() {
     let mut args = Args::parse();
     let _t = Timer::new(args.time);
 
-    if let Some(log) = args.log_file {
+    let level = match args.verbose {
+        0 => tracing::Level::INFO,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    if let Some(log) = &args.log_file {
         let log_file = File::create(log).unwrap();
         tracing_subscriber::fmt()
-            .with_max_level(tracing::Level::INFO)
+            .with_max_level(level)
             .with_ansi(false)
             .with_writer(log_file)
             .init();
+    } else if args.verbose > 0 || !args.print_function.is_empty() || args.function_times.is_some() {
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(std::io::stderr)
+            .init();
     }
 
     let path = if let Some(output) = &mut args.output {
@@ -83,26 +214,75 @@ fn main() {
         return;
     }
 
+    let compile = compile_util::CompileOptions {
+        edition: args.edition.parse().unwrap(),
+        target: args.target,
+        cfgs: args.cfg,
+        sysroot: args.sysroot,
+    };
     let conf = ai::analysis::AnalysisConfig {
         max_loop_head_states: args.max_loop_head_states.unwrap_or(usize::MAX),
         widening: !args.no_widening,
-        verbose: args.verbose,
         print_functions: args.print_function.into_iter().collect(),
         function_times: args.function_times,
+        min_output_params: args.min_params.unwrap_or(1),
+        function_filter: args.function_regex,
+        file_filter: args.file_glob,
+        mut_refs: args.mut_refs,
+        include_tests: args.include_tests,
+        module_prefix: args.module_prefix.clone(),
+        max_file_size: args.max_file_size,
+        max_function_size: args.max_function_size,
+        compile: compile.clone(),
     };
+
+    if let Some(names) = args.print_mir {
+        let names = names.into_iter().collect();
+        let mir = ai::analysis::mir_path(path, &names, &conf).unwrap_or_else(|e| exit_with(e));
+        if let Err(e) = std::io::stdout().write_all(mir.as_bytes()) {
+            eprintln!("failed to print MIR: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if args.check_only {
+        let result = ai::analysis::check_path(path, &conf).unwrap_or_else(|e| exit_with(e));
+        if let Some(result) = result {
+            print!("{}", ai::analysis::DisplayResult(&result));
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.stats {
+        let stats = ai::analysis::stats_path(path, &conf).unwrap_or_else(|e| exit_with(e));
+        if args.format == Format::Json {
+            println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+        } else {
+            print_counts("total", &stats.total);
+            for (reason, n) in &stats.skipped {
+                println!("skipped ({}): {}", reason, n);
+            }
+            for (file, counts) in &stats.files {
+                print_counts(&file.to_string_lossy(), counts);
+            }
+            for (func, t) in &stats.analysis_times {
+                println!("{}: {}ms", func, t);
+            }
+        }
+        return;
+    }
+
     let analysis_result = if let Some(dump_file) = &args.use_analysis_result {
         let dump_file = File::open(dump_file).unwrap();
         serde_json::from_reader(dump_file).unwrap()
     } else {
-        ai::analysis::analyze_path(path, &conf)
+        ai::analysis::analyze_path(path, &conf).unwrap_or_else(|e| exit_with(e))
     };
 
-    if args.verbose {
-        for (func, params) in &analysis_result {
-            println!("{}", func);
-            for param in params {
-                println!("  {:?}", param);
-            }
+    for (func, params) in &analysis_result {
+        for param in params {
+            tracing::debug!("{} {:?}", func, param);
         }
     }
 
@@ -126,7 +306,17 @@ fn main() {
         return;
     }
 
-    if args.use_analysis_result.is_none() {
+    if args.format == Format::Json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&analysis_result).unwrap()
+        );
+    } else if args.use_analysis_result.is_none() {
+        if args.sort_by == SortBy::Score {
+            print!("{}", ai::analysis::DisplayRanked(&analysis_result));
+        } else {
+            print!("{}", ai::analysis::DisplayResult(&analysis_result));
+        }
         let fns = analysis_result.len();
         let musts = analysis_result
             .values()
@@ -144,11 +334,66 @@ fn main() {
         serde_json::to_writer_pretty(dump_file, &analysis_result).unwrap();
     }
 
-    if !args.transform {
+    if let Some(dot_file) = args.call_graph {
+        let graph = ai::analysis::call_graph_path(path, &analysis_result, &conf)
+            .unwrap_or_else(|e| exit_with(e));
+        fs::write(dot_file, graph.to_dot()).unwrap();
+    }
+
+    if !args.transform && args.report_only.is_none() {
         return;
     }
 
-    transform::transform_path(path, &analysis_result);
+    let conf = transform::TransformConfig {
+        transform_calls: !args.no_transform_calls,
+        allow_abi_break: args.allow_abi_break,
+        narrow_bool_outputs: args.narrow_bool_outputs,
+        errno_success: args.errno_to_result,
+        init_strategy: match args.init_strategy {
+            Init::Zero => transform::InitStrategy::ZeroInit,
+            Init::MaybeUninit => transform::InitStrategy::MaybeUninit,
+            Init::Default => transform::InitStrategy::DefaultTrait,
+        },
+        write_back_style: match args.write_back_style {
+            WriteBack::Tuple => transform::WriteBackStyle::Tuple,
+            WriteBack::Struct => transform::WriteBackStyle::Struct,
+        },
+        only: (!args.only.is_empty()).then(|| args.only.into_iter().collect()),
+        skip: args.skip.into_iter().collect(),
+        module_prefix: args.module_prefix,
+        generate_wrapper: args.generate_wrapper,
+        keep_wrapper: args.keep_wrapper,
+        rustfmt: args.fmt,
+        compile,
+    };
+    if let Some(report_file) = args.report_only {
+        let report =
+            transform::report_path(path, &analysis_result, &conf).unwrap_or_else(|e| exit_with(e));
+        let report_file = File::create(report_file).unwrap();
+        serde_json::to_writer_pretty(report_file, &report).unwrap();
+        return;
+    }
+    let skipped = if args.verify {
+        transform::transform_path_verified(path, &analysis_result, &conf)
+    } else {
+        transform::transform_path(path, &analysis_result, &conf)
+    }
+    .unwrap_or_else(|e| exit_with(e));
+    for (name, reason) in skipped {
+        eprintln!("{} is not transformed because {}", name, reason);
+    }
+}
+
+fn print_counts(name: &str, counts: &ai::analysis::Counts) {
+    println!(
+        "{}: {} functions, {} with output params, {} must, {} may",
+        name, counts.functions, counts.with_output_params, counts.musts, counts.mays
+    );
+}
+
+fn exit_with(e: compile_util::CompilerError) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1)
 }
 
 fn clear_dir(path: &Path) {
@@ -1,22 +1,28 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs,
+    io::Write as _,
+    ops::Range,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Stdio},
     sync::{Arc, Mutex},
 };
 
-use etrace::ok_or;
+use etrace::{ok_or, some_or};
 use rustc_data_structures::sync::Lrc;
 use rustc_errors::{
-    emitter::Emitter, registry::Registry, translation::Translate, FluentBundle, Handler, Level,
+    emitter::Emitter,
+    fallback_fluent_bundle,
+    registry::Registry,
+    translation::{to_fluent_args, Translate},
+    FluentBundle, Handler, LazyFallbackBundle, Level,
 };
 use rustc_feature::UnstableFeatures;
 use rustc_hash::{FxHashMap, FxHashSet};
 use rustc_interface::Config;
 use rustc_middle::ty::TyCtxt;
 use rustc_session::{
-    config::{CheckCfg, CrateType, ErrorOutputType, Input, Options},
+    config::{host_triple, CheckCfg, CrateType, ErrorOutputType, Input, Options},
     EarlyErrorHandler,
 };
 use rustc_span::{
@@ -24,31 +30,133 @@ use rustc_span::{
     source_map::{FileName, SourceMap},
     RealFileName, Span,
 };
+use rustc_target::spec::TargetTriple;
 use rustfix::{LinePosition, LineRange, Replacement, Snippet, Solution, Suggestion};
 
-pub fn run_compiler<R: Send, F: FnOnce(TyCtxt<'_>) -> R + Send>(config: Config, f: F) -> Option<R> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerError {
+    /// The messages of the errors, each prefixed with its location.
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "compilation failed with {} error(s)", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompilerError {}
+
+pub fn run_compiler<R: Send, F: FnOnce(TyCtxt<'_>) -> R + Send>(
+    mut config: Config,
+    f: F,
+) -> Result<R, CompilerError> {
+    let errors = Arc::new(Mutex::new(vec![]));
+    let errors_clone = errors.clone();
+    config.parse_sess_created = Some(Box::new(|ps| {
+        let emitter = CollectingEmitter {
+            errors: errors_clone,
+            source_map: ps.clone_source_map(),
+            bundle: fallback_fluent_bundle(
+                rustc_driver_impl::DEFAULT_LOCALE_RESOURCES.to_vec(),
+                false,
+            ),
+        };
+        ps.span_diagnostic = Handler::with_emitter(Box::new(emitter));
+    }));
     rustc_driver::catch_fatal_errors(|| {
         rustc_interface::run_compiler(config, |compiler| {
-            compiler.enter(|queries| queries.global_ctxt().ok()?.enter(|tcx| Some(f(tcx))))
+            compiler.enter(|queries| {
+                queries.global_ctxt().ok()?.enter(|tcx| {
+                    // borrowck errors are tolerated
+                    tcx.hir()
+                        .par_body_owners(|def_id| tcx.ensure().typeck(def_id));
+                    if tcx.sess.has_errors().is_some() {
+                        return None;
+                    }
+                    Some(f(tcx))
+                })
+            })
         })
     })
-    .ok()?
+    .ok()
+    .flatten()
+    .ok_or_else(|| CompilerError {
+        errors: std::mem::take(&mut *errors.lock().unwrap()),
+    })
+}
+
+/// How the input is compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileOptions {
+    pub edition: Edition,
+    /// The target triple, or the host if `None`. The sysroot must contain the standard library
+    /// of the target unless the input is `#![no_core]`.
+    pub target: Option<String>,
+    /// `cfg` flags in the form of `name` or `name="value"`, as passed to `rustc --cfg`.
+    pub cfgs: Vec<String>,
+    /// The sysroot, or `$NOPCRAT_SYSROOT` or that of the current toolchain if `None`.
+    pub sysroot: Option<PathBuf>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            edition: Edition::Edition2021,
+            target: None,
+            cfgs: vec![],
+            sysroot: None,
+        }
+    }
+}
+
+impl CompileOptions {
+    fn sysroot(&self) -> PathBuf {
+        self.sysroot
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(sys_root()))
+    }
+
+    fn crate_cfg(&self) -> FxHashSet<(String, Option<String>)> {
+        self.cfgs
+            .iter()
+            .map(|cfg| match cfg.split_once('=') {
+                Some((name, value)) => (
+                    name.trim().to_string(),
+                    Some(value.trim().trim_matches('"').to_string()),
+                ),
+                None => (cfg.trim().to_string(), None),
+            })
+            .collect()
+    }
 }
 
 pub fn make_config(input: Input) -> Config {
+    make_config_with(input, &CompileOptions::default())
+}
+
+pub fn make_config_with(input: Input, options: &CompileOptions) -> Config {
     let opts = find_deps();
     Config {
         opts: Options {
-            maybe_sysroot: Some(PathBuf::from(sys_root())),
+            maybe_sysroot: Some(options.sysroot()),
             search_paths: opts.search_paths,
             externs: opts.externs,
             unstable_features: UnstableFeatures::Allow,
             crate_types: vec![CrateType::Rlib],
             debug_assertions: false,
-            edition: Edition::Edition2021,
+            edition: options.edition,
+            target_triple: options.target.as_deref().map_or_else(
+                || TargetTriple::from_triple(host_triple()),
+                TargetTriple::from_triple,
+            ),
             ..Options::default()
         },
-        crate_cfg: FxHashSet::default(),
+        crate_cfg: options.crate_cfg(),
         crate_check_cfg: CheckCfg::default(),
         input,
         output_dir: None,
@@ -67,19 +175,132 @@ pub fn make_config(input: Input) -> Config {
     }
 }
 
-pub fn make_counting_config(input: Input) -> (Config, Arc<Mutex<usize>>) {
-    let mut config = make_config(input);
-    let arc = Arc::new(Mutex::new(0));
-    let emitter = CountingEmitter(arc.clone());
-    config.parse_sess_created = Some(Box::new(|ps| {
-        ps.span_diagnostic = Handler::with_emitter(Box::new(emitter));
-    }));
-    (config, arc)
+/// Uses the edition, crate type, default features, and dependencies of the target of the package
+/// at `manifest_path` whose root is `file`, or of its first target if no root matches. The
+/// dependencies must have been built by cargo. Falls back to `make_config` if `cargo metadata`
+/// fails.
+pub fn make_config_from_manifest(manifest_path: &Path, file: &Path) -> Config {
+    let mut config = make_config(path_to_input(file));
+    let manifest = some_or!(CargoManifest::read(manifest_path, file), return config);
+    let deps = find_deps_in(&manifest.deps_dir, Some(&manifest.dependencies));
+    config.opts.search_paths = deps.search_paths;
+    config.opts.externs = deps.externs;
+    config.opts.edition = manifest.edition;
+    config.opts.crate_types = manifest.crate_types;
+    config.crate_cfg = manifest
+        .features
+        .into_iter()
+        .map(|f| ("feature".to_string(), Some(f)))
+        .collect();
+    config
+}
+
+struct CargoManifest {
+    edition: Edition,
+    crate_types: Vec<CrateType>,
+    features: BTreeSet<String>,
+    dependencies: BTreeSet<String>,
+    deps_dir: PathBuf,
+}
+
+impl CargoManifest {
+    fn read(manifest_path: &Path, file: &Path) -> Option<Self> {
+        let output = Command::new("cargo")
+            .arg("metadata")
+            .arg("--format-version=1")
+            .arg("--no-deps")
+            .arg("--offline")
+            .arg("--manifest-path")
+            .arg(manifest_path)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            tracing::warn!(
+                "cargo metadata failed for {}: {}",
+                manifest_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+
+        let manifest_path = manifest_path.canonicalize().ok()?;
+        let packages = metadata["packages"].as_array()?;
+        let package = packages
+            .iter()
+            .find(|p| {
+                p["manifest_path"]
+                    .as_str()
+                    .and_then(|m| Path::new(m).canonicalize().ok())
+                    == Some(manifest_path.clone())
+            })
+            .or(packages.first())?;
+
+        let file = file.canonicalize().ok();
+        let targets = package["targets"].as_array()?;
+        let target = targets
+            .iter()
+            .find(|t| {
+                t["src_path"]
+                    .as_str()
+                    .and_then(|p| Path::new(p).canonicalize().ok())
+                    == file
+            })
+            .or(targets.first())?;
+
+        let edition = target["edition"].as_str()?.parse().ok()?;
+        let crate_types = target["crate_types"]
+            .as_array()?
+            .iter()
+            .filter_map(|t| match t.as_str()? {
+                "bin" => Some(CrateType::Executable),
+                "lib" | "rlib" => Some(CrateType::Rlib),
+                "dylib" => Some(CrateType::Dylib),
+                "cdylib" => Some(CrateType::Cdylib),
+                "staticlib" => Some(CrateType::Staticlib),
+                "proc-macro" => Some(CrateType::ProcMacro),
+                _ => None,
+            })
+            .collect();
+
+        let mut features = BTreeSet::new();
+        let mut work_list = vec!["default"];
+        while let Some(feature) = work_list.pop() {
+            let enabled = some_or!(package["features"][feature].as_array(), continue);
+            for f in enabled.iter().filter_map(|f| f.as_str()) {
+                // `dep:x` and `x/y` enable optional dependencies, not features of this package
+                if !f.contains(':') && !f.contains('/') && features.insert(f.to_string()) {
+                    work_list.push(f);
+                }
+            }
+        }
+
+        let dependencies = package["dependencies"]
+            .as_array()?
+            .iter()
+            .filter(|d| d["kind"].is_null())
+            .filter_map(|d| d["rename"].as_str().or(d["name"].as_str()))
+            .map(|d| d.replace('-', "_"))
+            .collect();
+
+        let deps_dir = Path::new(metadata["target_directory"].as_str()?).join("debug/deps");
+
+        Some(Self {
+            edition,
+            crate_types,
+            features,
+            dependencies,
+            deps_dir,
+        })
+    }
 }
 
+/// The file name of code given as a string, as it appears in the results.
+pub const CODE_FILE_NAME: &str = "main.rs";
+
 pub fn str_to_input(code: &str) -> Input {
     Input::Str {
-        name: FileName::Custom("main.rs".to_string()),
+        name: FileName::Custom(CODE_FILE_NAME.to_string()),
         input: code.to_string(),
     }
 }
@@ -89,21 +310,154 @@ pub fn path_to_input(path: &Path) -> Input {
 }
 
 pub fn span_to_path(span: Span, source_map: &SourceMap) -> Option<PathBuf> {
-    if let FileName::Real(RealFileName::LocalPath(p)) = source_map.span_to_filename(span) {
-        Some(p)
-    } else {
-        None
+    match source_map.span_to_filename(span) {
+        FileName::Real(RealFileName::LocalPath(p)) => Some(p),
+        FileName::Custom(name) => Some(PathBuf::from(name)),
+        _ => None,
     }
 }
 
+/// Files whose contents no longer match the snippets of their suggestions, e.g., because they
+/// have already been transformed, are left untouched.
 pub fn apply_suggestions<P: AsRef<Path>>(suggestions: &BTreeMap<P, Vec<Suggestion>>) {
     for (path, suggestions) in suggestions {
+        let path = path.as_ref();
         let code = String::from_utf8(fs::read(path).unwrap()).unwrap();
+        let stale = suggestions
+            .iter()
+            .flat_map(|s| &s.snippets)
+            .find(|snippet| code.get(snippet.range.clone()) != Some(&snippet.text.1));
+        if let Some(snippet) = stale {
+            tracing::warn!(
+                "{} is not modified because it has changed at line {}",
+                path.display(),
+                snippet.line_range.start.line
+            );
+            continue;
+        }
+        let conflicts = SuggestionsConflictDetector::check_file(path, suggestions);
+        if !conflicts.is_empty() {
+            for conflict in &conflicts {
+                tracing::warn!("{}", conflict);
+            }
+            tracing::warn!(
+                "{} is not modified because its suggestions conflict",
+                path.display()
+            );
+            continue;
+        }
         let fixed = rustfix::apply_suggestions(&code, suggestions).unwrap();
         fs::write(path, fixed.as_bytes()).unwrap();
     }
 }
 
+/// Formats the file at `path` with `rustfmt`, leaving the modules it declares untouched. If
+/// `rustfmt` fails, e.g., because the file does not parse, the file is kept as it is.
+pub fn rustfmt(path: &Path, options: &CompileOptions) -> bool {
+    let code = ok_or!(fs::read(path), return false);
+    let sysroot_rustfmt = options.sysroot().join("bin").join("rustfmt");
+    let rustfmt = if sysroot_rustfmt.exists() {
+        sysroot_rustfmt
+    } else {
+        PathBuf::from("rustfmt")
+    };
+    // formatting stdin does not follow `mod` declarations to other files
+    let child = Command::new(rustfmt)
+        .arg(format!("--edition={}", options.edition))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = ok_or!(child, err, {
+        tracing::warn!("failed to run rustfmt: {}", err);
+        return false;
+    });
+    child.stdin.take().unwrap().write_all(&code).unwrap();
+    let output = child.wait_with_output().unwrap();
+    if !output.status.success() {
+        tracing::warn!(
+            "{} is not formatted because rustfmt failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return false;
+    }
+    fs::write(path, output.stdout).unwrap();
+    true
+}
+
+/// Two suggestions that replace overlapping parts of a file. An insertion conflicts with a
+/// replacement only if it is strictly inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: PathBuf,
+    pub first: Range<usize>,
+    pub second: Range<usize>,
+    /// The line where `second` starts.
+    pub line: usize,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "suggestions for bytes {:?} and {:?} of {} (line {}) overlap",
+            self.first,
+            self.second,
+            self.path.display(),
+            self.line
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SuggestionsConflictDetector;
+
+impl SuggestionsConflictDetector {
+    pub fn check<P: AsRef<Path>>(suggestions: &BTreeMap<P, Vec<Suggestion>>) -> Vec<Conflict> {
+        suggestions
+            .iter()
+            .flat_map(|(path, suggestions)| Self::check_file(path.as_ref(), suggestions))
+            .collect()
+    }
+
+    fn check_file(path: &Path, suggestions: &[Suggestion]) -> Vec<Conflict> {
+        let mut snippets: Vec<_> = suggestions
+            .iter()
+            .flat_map(|s| &s.solutions)
+            .flat_map(|s| &s.replacements)
+            .map(|r| &r.snippet)
+            .collect();
+        snippets.sort_by_key(|s| (s.range.start, s.range.end));
+        let mut conflicts = vec![];
+        // the snippet reaching farthest among the ones seen so far
+        let mut farthest: Option<&Snippet> = None;
+        for snippet in snippets {
+            if let Some(prev) = farthest {
+                let (r1, r2) = (&prev.range, &snippet.range);
+                let overlaps = if r2.is_empty() || r1.is_empty() {
+                    r1.start < r2.start && r2.start < r1.end
+                } else {
+                    r2.start < r1.end
+                };
+                if overlaps {
+                    conflicts.push(Conflict {
+                        path: path.to_path_buf(),
+                        first: r1.clone(),
+                        second: r2.clone(),
+                        line: snippet.line_range.start.line,
+                    });
+                }
+                if r2.end <= r1.end {
+                    continue;
+                }
+            }
+            farthest = Some(snippet);
+        }
+        conflicts
+    }
+}
+
 pub fn make_suggestion(snippet: Snippet, replacement: String) -> Suggestion {
     let replacement = Replacement {
         snippet: snippet.clone(),
@@ -149,27 +503,41 @@ pub fn span_to_snippet(span: Span, source_map: &SourceMap) -> Snippet {
     }
 }
 
-struct CountingEmitter(Arc<Mutex<usize>>);
+/// Collects the messages of errors so that they can be returned in `CompilerError`.
+struct CollectingEmitter {
+    errors: Arc<Mutex<Vec<String>>>,
+    source_map: Lrc<SourceMap>,
+    bundle: LazyFallbackBundle,
+}
 
-impl Translate for CountingEmitter {
+impl Translate for CollectingEmitter {
     fn fluent_bundle(&self) -> Option<&Lrc<FluentBundle>> {
         None
     }
 
     fn fallback_fluent_bundle(&self) -> &FluentBundle {
-        panic!()
+        &self.bundle
     }
 }
 
-impl Emitter for CountingEmitter {
+impl Emitter for CollectingEmitter {
     fn emit_diagnostic(&mut self, diag: &rustc_errors::Diagnostic) {
-        if matches!(diag.level(), Level::Error { .. }) {
-            *self.0.lock().unwrap() += 1;
+        if !matches!(diag.level(), Level::Error { .. }) {
+            return;
         }
+        let args = to_fluent_args(diag.args());
+        let message = self.translate_messages(&diag.message, &args);
+        let error = if let Some(span) = diag.span.primary_span() {
+            let location = self.source_map.span_to_diagnostic_string(span);
+            format!("{}: {}", location, message)
+        } else {
+            message.to_string()
+        };
+        self.errors.lock().unwrap().push(error);
     }
 
     fn source_map(&self) -> Option<&Lrc<SourceMap>> {
-        None
+        Some(&self.source_map)
     }
 }
 
@@ -194,13 +562,18 @@ impl Emitter for SilentEmitter {
 }
 
 fn find_deps() -> Options {
-    let mut args = vec!["a.rs".to_string()];
-
     let dir = std::env::var("DIR").unwrap_or_else(|_| ".".to_string());
     let dep = format!("{}/deps_crate/target/debug/deps", dir);
-    if let Ok(dir) = std::fs::read_dir(&dep) {
+    find_deps_in(Path::new(&dep), None)
+}
+
+/// Passes every rlib in `dep` as an extern, or only those in `names` if given.
+fn find_deps_in(dep: &Path, names: Option<&BTreeSet<String>>) -> Options {
+    let mut args = vec!["a.rs".to_string()];
+
+    if let Ok(dir) = std::fs::read_dir(dep) {
         args.push("-L".to_string());
-        args.push(format!("dependency={}", dep));
+        args.push(format!("dependency={}", dep.display()));
 
         for f in dir {
             let f = ok_or!(f, continue);
@@ -210,7 +583,10 @@ fn find_deps() -> Options {
             }
             let i = f.find('-').unwrap();
             let name = f[3..i].to_string();
-            let d = format!("{}={}/{}", name, dep, f);
+            if names.map_or(false, |names| !names.contains(&name)) {
+                continue;
+            }
+            let d = format!("{}={}/{}", name, dep.display(), f);
             args.push("--extern".to_string());
             args.push(d);
         }
@@ -221,8 +597,9 @@ fn find_deps() -> Options {
     rustc_session::config::build_session_options(&mut handler, &matches)
 }
 
-fn sys_root() -> String {
-    std::env::var("SYSROOT")
+pub(crate) fn sys_root() -> String {
+    std::env::var("NOPCRAT_SYSROOT")
+        .or_else(|_| std::env::var("SYSROOT"))
         .ok()
         .map(PathBuf::from)
         .or_else(|| {
@@ -267,3 +644,121 @@ fn toolchain_path(home: Option<String>, toolchain: Option<String>) -> Option<Pat
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(range: Range<usize>) -> Suggestion {
+        let position = LinePosition { line: 1, column: 0 };
+        let snippet = Snippet {
+            file_name: "a.rs".to_string(),
+            line_range: LineRange {
+                start: position,
+                end: position,
+            },
+            range,
+            text: ("".into(), "".into(), "".into()),
+        };
+        make_suggestion(snippet, "".to_string())
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let check = |ranges: &[Range<usize>]| {
+            let suggestions = BTreeMap::from([(
+                PathBuf::from("a.rs"),
+                ranges.iter().cloned().map(suggestion).collect::<Vec<_>>(),
+            )]);
+            SuggestionsConflictDetector::check(&suggestions)
+                .into_iter()
+                .map(|c| (c.first, c.second))
+                .collect::<Vec<_>>()
+        };
+        // adjacent replacements and insertions at their ends
+        assert!(check(&[0..3, 3..5, 3..3, 5..5, 5..5]).is_empty());
+        assert_eq!(check(&[0..3, 2..5]), vec![(0..3, 2..5)]);
+        assert_eq!(
+            check(&[0..10, 2..3, 4..4]),
+            vec![(0..10, 2..3), (0..10, 4..4)]
+        );
+        assert_eq!(check(&[1..3, 1..3]), vec![(1..3, 1..3)]);
+    }
+
+    #[test]
+    fn test_rustfmt() {
+        let dir = std::env::temp_dir().join(format!("nopcrat-rustfmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.rs");
+        fs::write(&path, "mod b;\nfn f() -> i32 { ({ let x = 1;  x }) }\n").unwrap();
+        assert!(rustfmt(&path, &CompileOptions::default()));
+        let formatted = fs::read_to_string(&path).unwrap();
+        assert!(formatted.starts_with("mod b;\nfn f() -> i32 {\n"));
+
+        let broken = "fn f() -> i32 { ({ let x = 1;  x } }\n";
+        fs::write(&path, broken).unwrap();
+        assert!(!rustfmt(&path, &CompileOptions::default()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sysroot() {
+        let config = make_config(str_to_input(""));
+        assert_eq!(config.opts.maybe_sysroot, Some(PathBuf::from(sys_root())));
+
+        let options = CompileOptions {
+            sysroot: Some(PathBuf::from("/nopcrat/sysroot")),
+            ..CompileOptions::default()
+        };
+        let config = make_config_with(str_to_input(""), &options);
+        assert_eq!(config.opts.maybe_sysroot, options.sysroot);
+    }
+
+    #[test]
+    fn test_config_from_manifest() {
+        let dir = std::env::temp_dir().join(format!("nopcrat-manifest-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = dir.join("Cargo.toml");
+        fs::write(
+            &manifest,
+            r#"
+[package]
+name = "m"
+version = "0.1.0"
+edition = "2015"
+
+[features]
+default = ["a"]
+a = ["b"]
+b = []
+c = []
+"#,
+        )
+        .unwrap();
+        let lib = dir.join("src/lib.rs");
+        fs::write(
+            &lib,
+            "
+            #[cfg(all(feature = \"b\", not(feature = \"c\")))]
+            pub fn f() {}
+            ",
+        )
+        .unwrap();
+
+        let config = make_config_from_manifest(&manifest, &lib);
+        let (edition, fns) = run_compiler(config, |tcx| {
+            let fns: Vec<_> = tcx
+                .hir()
+                .items()
+                .map(|id| tcx.hir().item(id).ident.to_string())
+                .filter(|name| name == "f")
+                .collect();
+            (tcx.sess.edition(), fns)
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(edition, Edition::Edition2015);
+        assert_eq!(fns, vec!["f"]);
+    }
+}
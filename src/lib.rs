@@ -45,11 +45,14 @@ extern crate rustc_feature;
 extern crate rustc_hash;
 extern crate rustc_hir;
 extern crate rustc_index;
+extern crate rustc_infer;
 extern crate rustc_interface;
 extern crate rustc_middle;
 extern crate rustc_mir_dataflow;
 extern crate rustc_session;
 extern crate rustc_span;
+extern crate rustc_target;
+extern crate rustc_trait_selection;
 extern crate rustc_type_ir;
 
 pub mod ai;
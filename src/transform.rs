@@ -5,59 +5,471 @@ use std::{
 
 use etrace::some_or;
 use rustc_ast::LitKind;
+use rustc_hir as hir;
 use rustc_hir::{
-    def::Res, intravisit::Visitor as HVisitor, BinOpKind, Expr, ExprKind, FnRetTy, HirId, ItemKind,
-    MutTy, Node, PatKind, QPath, TyKind,
+    def::{DefKind, Res},
+    intravisit::Visitor as HVisitor,
+    BinOpKind, BindingAnnotation, BodyId, BorrowKind, Expr, ExprKind, FnRetTy, FnSig, HirId,
+    ImplItemKind, ItemKind, MutTy, Mutability, Node, PatKind, QPath, Stmt, StmtKind, TraitFn,
+    TraitItemKind, TyKind, UnOp,
 };
+use rustc_infer::infer::TyCtxtInferExt;
 use rustc_middle::{
     hir::nested_filter,
-    mir::{BasicBlock, TerminatorKind},
-    ty::TyCtxt,
+    mir::{
+        BasicBlock, Body, Local, Operand, ProjectionElem, Rvalue, StatementKind, TerminatorKind,
+    },
+    ty::{
+        print::{with_crate_prefix, with_no_trimmed_paths},
+        GenericArgKind, Ty, TyCtxt,
+    },
 };
-use rustc_span::{def_id::DefId, source_map::SourceMap, BytePos, Span};
+use rustc_span::{def_id::DefId, source_map::SourceMap, sym, BytePos, Span, DUMMY_SP};
+use rustc_trait_selection::infer::InferCtxtExt;
 use rustfix::Suggestion;
+use serde::{Deserialize, Serialize};
 
-use crate::{ai::analysis::*, compile_util};
+use crate::{ai::analysis::*, compile_util, compile_util::CompilerError};
 
-pub fn transform_path(path: &Path, params: &BTreeMap<String, Vec<OutputParam>>) {
+#[derive(Debug, Clone)]
+pub struct TransformConfig {
+    /// If false, only function signatures and bodies are rewritten, and call sites must be
+    /// updated manually.
+    pub transform_calls: bool,
+    /// If true, functions whose ABI is observable (`extern` or `#[no_mangle]`) are transformed
+    /// as well.
+    pub allow_abi_break: bool,
+    /// If true, must output params that only ever receive 0 or 1 are returned as `bool`.
+    pub narrow_bool_outputs: bool,
+    /// If set, a may output param is returned as `Result<T, _>` only when the function returns
+    /// this value exactly on the paths that write the param.
+    pub errno_success: Option<i128>,
+    pub init_strategy: InitStrategy,
+    pub write_back_style: WriteBackStyle,
+    /// If set, only the functions with these names (as in `def_path_str`) are transformed.
+    pub only: Option<BTreeSet<String>>,
+    /// Functions with these names are not transformed.
+    pub skip: BTreeSet<String>,
+    /// If set, only the functions in this module (e.g., `crate::ffi`) are transformed. Calls
+    /// to them are rewritten everywhere.
+    pub module_prefix: Option<String>,
+    /// If true, a function is left as it is but renamed to `{name}_raw`, and a new function
+    /// `{name}` with the transformed signature calls it. Only functions whose output params are
+    /// all must are transformed. Being unchanged, `#[no_mangle]` functions keep their symbols.
+    pub generate_wrapper: bool,
+    /// If true, a function is transformed into `{name}_inner`, and `{name}` keeps the original
+    /// signature, calling it and writing the results through the pointers. Call sites are left
+    /// untouched, and `#[no_mangle]` moves to the wrapper.
+    pub keep_wrapper: bool,
+    /// If true, each modified file is formatted with `rustfmt` when transformed on disk.
+    pub rustfmt: bool,
+    pub compile: compile_util::CompileOptions,
+}
+
+/// How a function returns multiple values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteBackStyle {
+    #[default]
+    Tuple,
+    /// A struct named after the function, e.g., `FooOut` for `foo`, with a field for the
+    /// original return value (`rv`) and a field for each output param.
+    Struct,
+}
+
+/// How the local variable replacing an output param is initialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitStrategy {
+    /// Zero bytes, which is not a valid value of every type.
+    ZeroInit,
+    /// `MaybeUninit`, assumed to be initialized when returned.
+    #[default]
+    MaybeUninit,
+    /// `Default::default()` if the type implements `Default`, and `MaybeUninit` otherwise.
+    DefaultTrait,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        Self {
+            transform_calls: true,
+            allow_abi_break: false,
+            narrow_bool_outputs: false,
+            errno_success: None,
+            init_strategy: InitStrategy::default(),
+            write_back_style: WriteBackStyle::default(),
+            only: None,
+            skip: BTreeSet::new(),
+            module_prefix: None,
+            generate_wrapper: false,
+            keep_wrapper: false,
+            rustfmt: false,
+            compile: compile_util::CompileOptions::default(),
+        }
+    }
+}
+
+impl TransformConfig {
+    fn selects(&self, name: &str) -> bool {
+        self.only.as_ref().map_or(true, |only| only.contains(name))
+            && !self.skip.contains(name)
+            && self
+                .module_prefix
+                .as_ref()
+                .map_or(true, |prefix| has_path_prefix(name, prefix))
+    }
+}
+
+/// Returns the functions with output params that are not transformed and why.
+pub fn transform_path(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<BTreeMap<String, String>, CompilerError> {
+    let plan = plan_path(path, params, conf)?;
+    apply(&plan.suggestions);
+    if conf.rustfmt {
+        format_files(&plan.suggestions, conf);
+    }
+    Ok(plan.skipped)
+}
+
+fn format_files(suggestions: &BTreeMap<PathBuf, Vec<Suggestion>>, conf: &TransformConfig) {
+    for path in suggestions.keys() {
+        compile_util::rustfmt(path, &conf.compile);
+    }
+}
+
+fn plan_path(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<Plan, CompilerError> {
+    let input = compile_util::path_to_input(path);
+    let config = compile_util::make_config_with(input, &conf.compile);
+    compile_util::run_compiler(config, |tcx| transform(tcx, params, conf))
+}
+
+/// Like `transform_path`, but checks that the crate still compiles afterwards. If it does not,
+/// each function is transformed alone to find the ones breaking compilation, and the crate is
+/// transformed again without them. Returns the functions left untouched and why.
+pub fn transform_path_verified(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<BTreeMap<String, String>, CompilerError> {
+    let (compiled, skipped) = try_transform(path, params, conf)?;
+    if compiled {
+        return Ok(skipped);
+    }
+    let mut failed = vec![];
+    for name in params
+        .keys()
+        .filter(|name| conf.selects(name) && !skipped.contains_key(*name))
+    {
+        let conf = TransformConfig {
+            only: Some([name.clone()].into()),
+            ..conf.clone()
+        };
+        let suggestions = collect_suggestions(path, params, &conf)?;
+        if suggestions.is_empty() {
+            continue;
+        }
+        let originals = read_files(&suggestions);
+        apply(&suggestions);
+        if !compiles(path, &conf.compile) {
+            tracing::warn!(
+                "{} is not transformed because the result does not compile",
+                name
+            );
+            failed.push(name.clone());
+        }
+        write_files(&originals);
+    }
+    let mut conf = conf.clone();
+    conf.skip.extend(failed.iter().cloned());
+    let (compiled, mut skipped) = try_transform(path, params, &conf)?;
+    if !compiled {
+        // the remaining functions break compilation only when transformed together
+        failed.extend(
+            params
+                .keys()
+                .filter(|name| conf.selects(name) && !skipped.contains_key(*name))
+                .cloned(),
+        );
+    }
+    for name in failed {
+        skipped.insert(name, "the result does not compile".to_string());
+    }
+    Ok(skipped)
+}
+
+/// Transforms the crate at `path` and reverts the changes if it no longer compiles. Returns
+/// whether it compiles and the functions skipped by the transformation.
+fn try_transform(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<(bool, BTreeMap<String, String>), CompilerError> {
+    let plan = plan_path(path, params, conf)?;
+    let originals = read_files(&plan.suggestions);
+    apply(&plan.suggestions);
+    let compiled = compiles(path, &conf.compile);
+    if compiled {
+        if conf.rustfmt {
+            format_files(&plan.suggestions, conf);
+        }
+    } else {
+        write_files(&originals);
+    }
+    Ok((compiled, plan.skipped))
+}
+
+fn compiles(path: &Path, options: &compile_util::CompileOptions) -> bool {
+    let input = compile_util::path_to_input(path);
+    let config = compile_util::make_config_with(input, options);
+    compile_util::run_compiler(config, |tcx| tcx.analysis(()).is_ok()).unwrap_or(false)
+}
+
+fn read_files(suggestions: &BTreeMap<PathBuf, Vec<Suggestion>>) -> Vec<(PathBuf, Vec<u8>)> {
+    suggestions
+        .keys()
+        .map(|path| (path.clone(), std::fs::read(path).unwrap()))
+        .collect()
+}
+
+fn write_files(files: &[(PathBuf, Vec<u8>)]) {
+    for (path, contents) in files {
+        std::fs::write(path, contents).unwrap();
+    }
+}
+
+/// Computes the suggestions for transforming the crate at `path` without applying them.
+pub fn collect_suggestions(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<BTreeMap<PathBuf, Vec<Suggestion>>, CompilerError> {
+    Ok(plan_path(path, params, conf)?.suggestions)
+}
+
+/// What `transform_path` would do, without modifying any file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    /// Functions to be transformed and the indices of their output params.
+    pub functions: BTreeMap<String, Vec<usize>>,
+    /// Estimated from the lines that appear only in the original or only in the result.
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub warnings: Vec<String>,
+    /// Functions with output params that are not transformed and why.
+    pub skipped: BTreeMap<String, String>,
+    /// Calls to transformed functions that must be updated manually.
+    pub unrewritten_calls: usize,
+}
+
+pub fn report_path(
+    path: &Path,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<Report, CompilerError> {
     let input = compile_util::path_to_input(path);
-    let config = compile_util::make_config(input);
-    let suggestions = compile_util::run_compiler(config, |tcx| transform(tcx, params)).unwrap();
-    compile_util::apply_suggestions(&suggestions);
+    let config = compile_util::make_config_with(input, &conf.compile);
+    let plan = compile_util::run_compiler(config, |tcx| transform(tcx, params, conf))?;
+    Ok(make_report(plan, |path| {
+        std::fs::read_to_string(path).unwrap()
+    }))
+}
+
+pub fn report_code(
+    code: &str,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<Report, CompilerError> {
+    let input = compile_util::str_to_input(code);
+    let config = compile_util::make_config_with(input, &conf.compile);
+    let plan = compile_util::run_compiler(config, |tcx| transform(tcx, params, conf))?;
+    Ok(make_report(plan, |_| code.to_string()))
+}
+
+fn make_report<F: Fn(&Path) -> String>(plan: Plan, read: F) -> Report {
+    let mut report = Report {
+        functions: plan.functions,
+        warnings: plan.warnings,
+        skipped: plan.skipped,
+        unrewritten_calls: plan.unrewritten_calls,
+        ..Report::default()
+    };
+    for (path, suggestions) in &plan.suggestions {
+        let code = read(path);
+        let transformed = rustfix::apply_suggestions(&code, suggestions).unwrap();
+        let (added, removed) = line_diff(&code, &transformed);
+        report.lines_added += added;
+        report.lines_removed += removed;
+    }
+    report
+}
+
+/// Counts the lines only in `new` and the lines only in `old`, ignoring their order.
+fn line_diff(old: &str, new: &str) -> (usize, usize) {
+    let mut counts: BTreeMap<&str, isize> = BTreeMap::new();
+    for line in old.lines() {
+        *counts.entry(line).or_default() -= 1;
+    }
+    for line in new.lines() {
+        *counts.entry(line).or_default() += 1;
+    }
+    let added = counts.values().filter(|n| **n > 0).sum::<isize>();
+    let removed = -counts.values().filter(|n| **n < 0).sum::<isize>();
+    (added as usize, removed as usize)
+}
+
+/// Applies suggestions obtained from `collect_suggestions` to the files on disk.
+pub fn apply(suggestions: &BTreeMap<PathBuf, Vec<Suggestion>>) {
+    compile_util::apply_suggestions(suggestions);
+}
+
+pub fn transform_code(
+    code: &str,
+    params: &BTreeMap<String, Vec<OutputParam>>,
+    conf: &TransformConfig,
+) -> Result<String, CompilerError> {
+    let input = compile_util::str_to_input(code);
+    let config = compile_util::make_config_with(input, &conf.compile);
+    let suggestions =
+        compile_util::run_compiler(config, |tcx| transform(tcx, params, conf).suggestions)?;
+    let suggestions = some_or!(
+        suggestions.into_values().next(),
+        return Ok(code.to_string())
+    );
+    Ok(rustfix::apply_suggestions(code, &suggestions).unwrap())
+}
+
+#[derive(Default)]
+struct Plan {
+    suggestions: BTreeMap<PathBuf, Vec<Suggestion>>,
+    /// Transformed functions and the indices of their removed params.
+    functions: BTreeMap<String, Vec<usize>>,
+    warnings: Vec<String>,
+    /// Functions with output params that are not transformed and why.
+    skipped: BTreeMap<String, String>,
+    unrewritten_calls: usize,
+}
+
+impl Plan {
+    fn skip(&mut self, name: String, reason: String) {
+        let warning = format!("{} is not transformed because {}", name, reason);
+        tracing::info!("{}", warning);
+        self.warnings.push(warning);
+        self.skipped.insert(name, reason);
+    }
 }
 
 fn transform(
     tcx: TyCtxt<'_>,
     param_map: &BTreeMap<String, Vec<OutputParam>>,
-) -> BTreeMap<PathBuf, Vec<Suggestion>> {
+    conf: &TransformConfig,
+) -> Plan {
     let hir = tcx.hir();
     let source_map = tcx.sess.source_map();
 
-    let mut def_id_ty_map = BTreeMap::new();
-    for id in hir.items() {
-        let item = hir.item(id);
-        let ItemKind::TyAlias(ty, _) = item.kind else {
-            continue;
-        };
-        let TyKind::Ptr(MutTy { ty, .. }) = ty.kind else {
-            continue;
-        };
-        let def_id = item.owner_id.to_def_id();
-        let ty = source_map.span_to_snippet(ty.span).unwrap();
-        def_id_ty_map.insert(def_id, ty);
-    }
+    let fns = fn_items(tcx);
 
+    let mut plan = Plan::default();
     let mut funcs = BTreeMap::new();
-    for id in hir.items() {
-        let item = hir.item(id);
-        let ItemKind::Fn(sig, _, body_id) = item.kind else {
+    for FnItem {
+        def_id,
+        sig,
+        body_id,
+        in_trait,
+        ..
+    } in fns.iter().copied()
+    {
+        if in_trait {
             continue;
-        };
-        let def_id = id.owner_id.to_def_id();
+        }
         let name = tcx.def_path_str(def_id);
         let params = some_or!(param_map.get(&name), continue);
+        if !conf.selects(&name) {
+            tracing::info!("{} is not transformed because it is filtered out", name);
+            continue;
+        }
+        if tcx.def_span(def_id).from_expansion() {
+            plan.skip(name, "it is generated by a macro".to_string());
+            continue;
+        }
+        if brace_spans(hir.body(body_id), source_map).is_none() {
+            plan.skip(name, "its body is not a block in the source".to_string());
+            continue;
+        }
+        if let Some(p) = params.iter().find(|p| !p.fully_initialized) {
+            plan.skip(name, format!("param {} is only partially written", p.index));
+            continue;
+        }
+        if let Some(p) = params.iter().find(|p| p.loop_write) {
+            plan.skip(name, format!("param {} is only written in loops", p.index));
+            continue;
+        }
+        if let Some(p) = params.iter().find(|p| p.volatile) {
+            plan.skip(
+                name,
+                format!("param {} is accessed with volatile operations", p.index),
+            );
+            continue;
+        }
+        let wrapped = conf.generate_wrapper || conf.keep_wrapper;
+        if !conf.allow_abi_break && !wrapped && params.iter().any(|p| p.abi_sensitive) {
+            plan.skip(name, "its ABI is observable".to_string());
+            continue;
+        }
+        if conf.generate_wrapper && params.iter().any(|p| !p.must) {
+            let reason = "a wrapper cannot tell whether its may output params are written";
+            plan.skip(name, reason.to_string());
+            continue;
+        }
+        if wrapped && !tcx.generics_of(def_id).params.is_empty() {
+            let reason = "a wrapper of a generic function is not supported";
+            plan.skip(name, reason.to_string());
+            continue;
+        }
         let body = hir.body(body_id);
+        if let Some(p) = params
+            .iter()
+            .find(|p| !matches!(body.params[p.index].pat.kind, PatKind::Binding(..)))
+        {
+            plan.skip(
+                name,
+                format!("the pattern of param {} is not a binding", p.index),
+            );
+            continue;
+        }
         let mir_body = tcx.optimized_mir(def_id);
+        if let Some(p) = params.iter().find(|p| {
+            let local = Local::from_usize(p.index + 1);
+            escapes(mir_body, local, tcx, &mut BTreeSet::new())
+        }) {
+            plan.skip(name, format!("param {} escapes", p.index));
+            continue;
+        }
+        if let Some(i) = borrowed_by_return(def_id, params, tcx) {
+            plan.skip(name, format!("its return type borrows from param {}", i));
+            continue;
+        }
+        for (parent, child) in StructOverlapDetector::check(params, mir_body, tcx) {
+            plan.warnings.push(format!(
+                "param {} of {} may point to a field of param {}, so transforming both may change observable behavior",
+                child, name, parent
+            ));
+        }
+        for p in params.iter().filter(|p| !p.must) {
+            plan.warnings.push(format!(
+                "param {} of {} is not written on every path",
+                p.index, name
+            ));
+        }
+        plan.functions
+            .insert(name.clone(), params.iter().map(|p| p.index).collect());
+        let param_spans: Vec<_> = body.params.iter().map(|p| p.span).collect();
+        let removed: BTreeSet<_> = params.iter().map(|p| p.index).collect();
         let index_map: BTreeMap<_, _> = params
             .iter()
             .map(|param| {
@@ -65,8 +477,10 @@ fn transform(
                     index,
                     must,
                     complete_writes,
+                    bool_output,
                     ..
                 } = param;
+                // both include the receiver of a method, as does the index
                 let param = &body.params[*index];
 
                 let writes: Vec<_> = complete_writes
@@ -104,27 +518,53 @@ fn transform(
                         }
                     })
                     .collect();
-                let PatKind::Binding(_, hir_id, ident, _) = param.pat.kind else {
-                    unreachable!()
+                let PatKind::Binding(binding, hir_id, ident, _) = param.pat.kind else {
+                    unreachable!("checked above")
                 };
-                let span = to_comma(param.span, source_map);
+                let span = removal_span(&param_spans, *index, 0, &removed, source_map);
                 let name = ident.name.to_ident_string();
                 let ty = &sig.decl.inputs[*index];
+                let (wrapper, ty) = unwrap_ptr_ty(ty, tcx);
+                let is_ref = matches!(ty.kind, TyKind::Ref(..));
+                let local_ty = mir_body.local_decls[Local::from_usize(*index + 1)].ty;
+                let init = if conf.init_strategy == InitStrategy::DefaultTrait
+                    && !implements_default(local_ty, def_id, tcx)
+                {
+                    InitStrategy::MaybeUninit
+                } else {
+                    conf.init_strategy
+                };
+                // a pointer is zero-initialized as null
+                let null = pointee_ty(local_ty, true, tcx)
+                    .filter(|ty| ty.is_unsafe_ptr())
+                    .map(|ty| {
+                        if ty.is_mutable_ptr() {
+                            "std::ptr::null_mut()"
+                        } else {
+                            "std::ptr::null()"
+                        }
+                    });
                 let ty = match ty.kind {
-                    TyKind::Ptr(MutTy { ty, .. }) => source_map.span_to_snippet(ty.span).unwrap(),
-                    TyKind::Path(QPath::Resolved(_, path)) => {
-                        let Res::Def(_, def_id) = path.res else {
-                            unreachable!("{:?}", ty);
-                        };
-                        def_id_ty_map
-                            .get(&def_id)
-                            .expect(&format!("{:?}", ty))
-                            .clone()
+                    _ if matches!(wrapper, Wrapper::NonNull | Wrapper::OptionNonNull) => {
+                        source_map.span_to_snippet(ty.span).unwrap()
+                    }
+                    TyKind::Ptr(MutTy { ty, .. }) | TyKind::Ref(_, MutTy { ty, .. }) => {
+                        source_map.span_to_snippet(ty.span).unwrap()
                     }
-                    _ => unreachable!("{:?}", ty),
+                    // e.g., an alias from another module or crate
+                    _ => resolve_ptr_alias(ty, def_id, tcx).unwrap_or_else(|| {
+                        let ty = local_ty.builtin_deref(true).unwrap().ty;
+                        with_crate_prefix!(with_no_trimmed_paths!(ty.to_string()))
+                    }),
                 };
                 let param = Param {
                     must: *must,
+                    narrow: conf.narrow_bool_outputs && *must && *bool_output,
+                    is_ref,
+                    wrapper,
+                    mutable: binding.1 == Mutability::Mut,
+                    init,
+                    null,
                     writes,
                     write_args,
                     name,
@@ -141,29 +581,46 @@ fn transform(
             .map(|param| (param.hir_id, param))
             .collect();
         let mut remaining_return: Vec<_> = index_map.keys().copied().collect();
-        let first_return = SuccValue::find(params);
+        let first_return = SuccValue::find(params, conf.errno_success);
         if let Some((_, first)) = &first_return {
             remaining_return.retain(|i| i != first);
         }
         let is_unit = matches!(sig.decl.output, FnRetTy::DefaultReturn(_));
+        let values = !is_unit as usize + remaining_return.len();
+        let out_struct = if conf.write_back_style == WriteBackStyle::Struct && values > 1 {
+            Some(out_struct_name(def_id, tcx))
+        } else {
+            None
+        };
         let func = Func {
             is_unit,
             first_return,
             remaining_return,
             index_map,
             hir_id_map,
+            out_struct,
         };
         funcs.insert(def_id, func);
     }
 
     let mut suggestions: BTreeMap<_, Vec<_>> = BTreeMap::new();
-    for id in hir.items() {
-        let item = hir.item(id);
-        let ItemKind::Fn(sig, _, body_id) = item.kind else {
+    for FnItem {
+        def_id,
+        span: item_span,
+        sig,
+        body_id,
+        vis_span,
+        item_start,
+        ..
+    } in fns
+    {
+        let Some(file) = compile_util::span_to_path(item_span, source_map) else {
+            tracing::info!(
+                "{} is not transformed because it is not in a local file",
+                tcx.def_path_str(def_id)
+            );
             continue;
         };
-
-        let file = some_or!(compile_util::span_to_path(item.span, source_map), continue);
         let v = suggestions.entry(file).or_default();
         let mut fix = |span, code| {
             let snippet = compile_util::span_to_snippet(span, source_map);
@@ -171,28 +628,71 @@ fn transform(
             v.push(suggestion);
         };
 
-        let def_id = id.owner_id.to_def_id();
         let body = hir.body(body_id);
-        let curr = funcs.get(&def_id);
+        // a wrapped function is not rewritten, so its calls are transformed as in any other
+        let curr = funcs.get(&def_id).filter(|_| !conf.generate_wrapper);
 
         let mut visitor = BodyVisitor::new(tcx);
         visitor.visit_body(body);
 
         let mut ret_call_spans = BTreeSet::new();
         let mut call_spans = BTreeSet::new();
+        let mut manual_calls = String::new();
 
-        for call in visitor.calls {
+        // temporaries only passed to rewritten calls are removed along with their write-backs
+        let (calls, dead) = if conf.transform_calls && !conf.keep_wrapper {
+            let dead = visitor.dead_temporaries(&funcs);
+            (visitor.calls, dead)
+        } else {
+            (vec![], vec![])
+        };
+        let dead: BTreeSet<_> = dead
+            .into_iter()
+            .map(|(hir_id, span)| {
+                fix(span, "".to_string());
+                hir_id
+            })
+            .collect();
+        for call in calls {
             let Call {
                 hir_id,
                 span,
                 callee,
-                args,
+                method,
+                mut args,
             } = call;
             let func = some_or!(funcs.get(&callee), continue);
+            if span.from_expansion() {
+                let line = source_map.lookup_char_pos(span.source_callsite().lo()).line;
+                let callee = tcx.def_path_str(callee);
+                let warning = format!(
+                    "the call to {} in {} (line {}) is in a macro expansion and is not transformed",
+                    callee,
+                    tcx.def_path_str(def_id),
+                    line
+                );
+                tracing::warn!("{}", warning);
+                plan.warnings.push(warning);
+                plan.unrewritten_calls += 1;
+                manual_calls += &format!(
+                    "
+    compile_error!(\"nopcrat: cannot transform the call to {} at line {}; please update it manually\");",
+                    callee, line
+                );
+                continue;
+            }
             call_spans.insert(span);
 
-            for index in func.index_map.keys() {
-                let span = to_comma(args[*index].span, source_map);
+            let arg_spans: Vec<_> = args.iter().map(|arg| arg.span).collect();
+            let removed: BTreeSet<_> = func.index_map.keys().copied().collect();
+            for (i, arg) in args.iter_mut().enumerate() {
+                arg.dead = removed.contains(&i)
+                    && arg
+                        .borrowed
+                        .map_or(false, |(hir_id, _)| dead.contains(&hir_id));
+            }
+            for index in &removed {
+                let span = removal_span(&arg_spans, *index, method as usize, &removed, source_map);
                 fix(span, "".to_string());
             }
 
@@ -205,23 +705,21 @@ fn transform(
                     let if_span = if_span.with_hi(span.lo());
                     fix(if_span, "{ match ".to_string());
 
-                    let succ = "Ok(v___) => ";
-                    let fail = "Err(_) => ";
                     let (_, i) = func.first_return.as_ref().unwrap();
                     let arg = &args[*i];
+                    let succ = if arg.dead {
+                        "Ok(_) => "
+                    } else {
+                        "Ok(v___) => "
+                    };
+                    let fail = "Err(_) => ";
                     let set_flag = if let Some(arg) = assign_map.get(i) {
                         format!("{}___s = true;", arg)
                     } else {
                         "".to_string()
                     };
-                    let assign = if arg.code.contains("&mut ") {
-                        format!(" *({}) = v___; {}", arg.code, set_flag)
-                    } else {
-                        format!(
-                            " if !({0}).is_null() {{ *({0}) = v___; {1} }}",
-                            arg.code, set_flag
-                        )
-                    };
+                    let assign =
+                        format!(" {}", func.index_map[i].write_back(arg, "v___", &set_flag));
 
                     let bt = if then { succ } else { fail };
                     let bt_span = call.then_span.shrink_to_lo().with_lo(span.hi());
@@ -287,13 +785,14 @@ fn transform(
                 }
             }
 
-            let mut binding = func.call_binding();
+            let discarded = mtch.is_none() && is_discarded(hir_id, tcx);
+            let mut binding = func.call_binding(&args, discarded);
             if mtch.is_some() {
                 binding = "(match ".to_string() + &binding;
             }
             fix(span.shrink_to_lo(), binding);
 
-            let mut assign = func.call_assign(&args, &assign_map);
+            let mut assign = func.call_assign(&args, &assign_map, discarded);
             if let Some(m) = &mtch {
                 assign += m;
                 assign += ")";
@@ -301,7 +800,43 @@ fn transform(
             fix(span.shrink_to_hi(), assign);
         }
 
-        let func = some_or!(curr, continue);
+        let Some((body_start, body_end)) = brace_spans(body, source_map) else {
+            if !manual_calls.is_empty() {
+                tracing::warn!(
+                    "the body of {} is not a block in the source, so calls in macro expansions are not marked",
+                    tcx.def_path_str(def_id)
+                );
+            }
+            continue;
+        };
+
+        let Some(func) = curr else {
+            if !manual_calls.is_empty() {
+                fix(body_start, manual_calls);
+            }
+            if let Some(func) = funcs.get(&def_id) {
+                let wrapper = generate_wrapper(def_id, func, sig, body, vis_span, tcx);
+                let vis = source_map.span_to_snippet(vis_span).unwrap();
+                let vis = if vis.is_empty() { vis } else { vis + " " };
+                let orig = match sig.decl.output {
+                    FnRetTy::Return(ty) => Some(source_map.span_to_snippet(ty.span).unwrap()),
+                    FnRetTy::DefaultReturn(_) => None,
+                };
+                if let Some(def) = func.out_struct_def(orig, &vis) {
+                    fix(item_start, def);
+                }
+                for attr in hir.attrs(hir.local_def_id_to_hir_id(def_id.expect_local())) {
+                    if attr.has_name(sym::no_mangle) {
+                        let export = format!("#[export_name = \"{}\"]", tcx.item_name(def_id));
+                        fix(attr.span, export);
+                    }
+                }
+                let ident = tcx.def_ident_span(def_id).unwrap();
+                fix(ident.shrink_to_hi(), "_raw".to_string());
+                fix(item_span.shrink_to_hi(), wrapper);
+            }
+            continue;
+        };
         for param in func.params() {
             fix(param.span, "".to_string());
         }
@@ -314,34 +849,39 @@ fn transform(
             }
             FnRetTy::DefaultReturn(span) => (span, None),
         };
+        let vis = source_map.span_to_snippet(vis_span).unwrap();
+        let vis = if vis.is_empty() { vis } else { vis + " " };
+        if let Some(def) = func.out_struct_def(orig.clone(), &vis) {
+            fix(item_start, def);
+        }
         let ret_ty = func.return_type(orig);
         fix(span, format!("-> {}", ret_ty));
 
-        let local_vars: String = func
-            .params()
-            .map(|param| {
-                if param.must {
-                    format!(
-                        "
-    let mut {0}___v: {1} = std::mem::transmute([0u8; std::mem::size_of::<{1}>()]); \
-    let mut {0}: *mut {1} = &mut {0}___v;",
-                        param.name, param.ty,
-                    )
-                } else {
-                    format!(
-                        "
-    let mut {0}___s: bool = false; \
-    let mut {0}___v: {1} = std::mem::transmute([0u8; std::mem::size_of::<{1}>()]); \
-    let mut {0}: *mut {1} = &mut {0}___v;",
-                        param.name, param.ty,
-                    )
+        if conf.keep_wrapper {
+            let hir_id = hir.local_def_id_to_hir_id(def_id.expect_local());
+            let mut no_mangle = false;
+            for attr in hir.attrs(hir_id) {
+                if attr.has_name(sym::no_mangle) {
+                    no_mangle = true;
+                    fix(attr.span, "".to_string());
                 }
-            })
-            .collect();
+            }
+            let wrapper = keep_wrapper(def_id, func, sig, body, item_span, no_mangle, tcx);
+            let ident = tcx.def_ident_span(def_id).unwrap();
+            // the ABI is kept by the wrapper, and the inner function may return a tuple
+            let header = item_span.with_hi(ident.lo());
+            let header_s = source_map.span_to_snippet(header).unwrap();
+            if let (Some(lo), Some(hi)) = (header_s.find("extern"), header_s.rfind("fn")) {
+                let lo = header.lo() + BytePos(lo as u32);
+                let hi = header.lo() + BytePos(hi as u32);
+                fix(header.with_lo(lo).with_hi(hi), "".to_string());
+            }
+            fix(ident.shrink_to_hi(), "_inner".to_string());
+            fix(item_span.shrink_to_hi(), wrapper);
+        }
 
-        let pos = body.value.span.lo() + BytePos(1);
-        let span = body.value.span.with_lo(pos).with_hi(pos);
-        fix(span, local_vars);
+        let local_vars: String = func.params().map(|param| param.local_vars()).collect();
+        fix(body_start, manual_calls + &local_vars);
 
         for param in func.params() {
             for span in &param.writes {
@@ -366,10 +906,8 @@ fn transform(
         }
 
         if func.is_unit {
-            let pos = body.value.span.hi() - BytePos(1);
-            let span = body.value.span.with_lo(pos).with_hi(pos);
             let ret_v = func.return_value(None);
-            fix(span, ret_v);
+            fix(body_end, ret_v);
         }
     }
     suggestions.retain(|_, v| !v.is_empty());
@@ -382,100 +920,697 @@ fn transform(
             )
         });
     }
-    suggestions
-}
+    plan.suggestions = suggestions;
 
-#[derive(Debug, Clone)]
-struct Param {
-    must: bool,
-    writes: Vec<Span>,
-    write_args: BTreeMap<Span, usize>,
-    span: Span,
-    hir_id: HirId,
-    name: String,
-    ty: String,
+    // uses inside the crate are resolved to the original functions and rewritten, but users of
+    // a public re-export outside the crate still expect the old signatures
+    for id in hir.items() {
+        let item = hir.item(id);
+        let ItemKind::Use(path, _) = item.kind else {
+            continue;
+        };
+        if !tcx.visibility(item.owner_id).is_public() {
+            continue;
+        }
+        for res in &path.res {
+            let Res::Def(DefKind::Fn | DefKind::AssocFn, def_id) = res else {
+                continue;
+            };
+            let name = tcx.def_path_str(*def_id);
+            if !plan.functions.contains_key(&name) {
+                continue;
+            }
+            let line = source_map.lookup_char_pos(item.span.lo()).line;
+            plan.warnings.push(format!(
+                "{} is re-exported at line {}; keep a wrapper with the original signature if it is used outside the crate",
+                name, line
+            ));
+        }
+    }
+    plan
 }
 
-#[allow(unused)]
-struct Func {
-    is_unit: bool,
-    first_return: Option<(SuccValue, usize)>,
-    remaining_return: Vec<usize>,
-    index_map: BTreeMap<usize, Param>,
-    hir_id_map: BTreeMap<HirId, Param>,
+/// Returns the index of a removed param whose lifetime appears in the return type but in no
+/// kept param, e.g., the elided lifetime of `fn f(p: &mut i32) -> &i32`.
+fn borrowed_by_return<'tcx>(
+    def_id: DefId,
+    params: &[OutputParam],
+    tcx: TyCtxt<'tcx>,
+) -> Option<usize> {
+    let sig = tcx.fn_sig(def_id).skip_binder().skip_binder();
+    let regions = |ty: Ty<'tcx>| -> Vec<_> {
+        ty.walk()
+            .filter_map(|arg| match arg.unpack() {
+                GenericArgKind::Lifetime(r) if !r.is_static() => Some(r),
+                _ => None,
+            })
+            .collect()
+    };
+    let output = regions(sig.output());
+    let kept: Vec<_> = sig
+        .inputs()
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| params.iter().all(|p| p.index != *i))
+        .flat_map(|(_, ty)| regions(*ty))
+        .collect();
+    params.iter().map(|p| p.index).find(|i| {
+        regions(sig.inputs()[*i])
+            .iter()
+            .any(|r| output.contains(r) && !kept.contains(r))
+    })
 }
 
-impl Func {
-    fn params(&self) -> impl Iterator<Item = &Param> {
-        self.index_map.values()
+/// Finds pairs of output params that may point into the same struct.
+#[derive(Debug, Clone, Copy)]
+struct StructOverlapDetector;
+
+impl StructOverlapDetector {
+    /// Returns `(parent, child)` pairs of param indices where the pointee type of `child` is a
+    /// field type of the pointee type of `parent`.
+    fn check<'tcx>(
+        params: &[OutputParam],
+        body: &Body<'tcx>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Vec<(usize, usize)> {
+        let pointees: Vec<_> = params
+            .iter()
+            .filter_map(|p| {
+                let ty = body.local_decls[Local::from_usize(p.index + 1)].ty;
+                Some((p.index, pointee_ty(ty, true, tcx)?))
+            })
+            .collect();
+        let mut pairs = vec![];
+        for (parent, parent_ty) in &pointees {
+            for (child, child_ty) in &pointees {
+                if parent != child && Self::has_field_of(*parent_ty, *child_ty, tcx) {
+                    pairs.push((*parent, *child));
+                }
+            }
+        }
+        pairs
     }
 
-    fn cmp(&self, op: BinOpKind, target: u128) -> Option<bool> {
-        let (sv, _) = &self.first_return?;
-        let n = match *sv {
-            SuccValue::Int(n) => n,
-            SuccValue::Uint(n) => n as i128,
-            _ => return None,
+    fn has_field_of<'tcx>(ty: Ty<'tcx>, field_ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+        let rustc_middle::ty::Adt(adt_def, args) = ty.kind() else {
+            return false;
         };
-        let m = target as i128;
-        match op {
-            BinOpKind::Eq => Some(n == m),
-            BinOpKind::Ne => Some(n != m),
-            BinOpKind::Lt => Some(n < m),
-            BinOpKind::Le => Some(n <= m),
-            BinOpKind::Gt => Some(n > m),
-            BinOpKind::Ge => Some(n >= m),
-            _ => None,
-        }
+        adt_def.is_struct() && adt_def.all_fields().any(|f| f.ty(tcx, args) == field_ty)
     }
+}
 
-    fn call_binding(&self) -> String {
-        let mut xs = vec![];
-        if !self.is_unit {
-            xs.push("rv___".to_string());
-        }
-        for i in &self.remaining_return {
-            xs.push(format!("rv___{}", i));
-        }
-        if xs.len() == 1 {
-            format!("({{ let {} = ", xs.pop().unwrap())
-        } else {
-            mk_string(xs.iter(), "({ let (", ", ", ") = ")
-        }
+/// The empty spans just inside the braces of a function body, or `None` if the braces are not in
+/// the source as they are, e.g., because the body is expanded from a macro.
+fn brace_spans(body: &hir::Body<'_>, source_map: &SourceMap) -> Option<(Span, Span)> {
+    let span = body.value.span;
+    if span.from_expansion() || !matches!(body.value.kind, ExprKind::Block(..)) {
+        return None;
+    }
+    let snippet = source_map.span_to_snippet(span).ok()?;
+    if !snippet.starts_with('{') || !snippet.ends_with('}') {
+        return None;
     }
+    let lo = span.lo() + BytePos(1);
+    let hi = span.hi() - BytePos(1);
+    Some((span.with_lo(lo).with_hi(lo), span.with_lo(hi).with_hi(hi)))
+}
 
-    fn assign_map(&self, span: Span) -> BTreeMap<usize, String> {
-        let mut map = BTreeMap::new();
-        for p in self.params() {
-            let arg_idx = *some_or!(p.write_args.get(&span), continue);
-            map.insert(arg_idx, p.name.clone());
+/// A function with the transformed signature of `def_id` that calls the original one, renamed to
+/// `{name}_raw`, with pointers to its locals.
+fn generate_wrapper(
+    def_id: DefId,
+    func: &Func,
+    sig: &FnSig<'_>,
+    body: &hir::Body<'_>,
+    vis_span: Span,
+    tcx: TyCtxt<'_>,
+) -> String {
+    let source_map = tcx.sess.source_map();
+    let name = tcx.item_name(def_id);
+    let mut params = vec![];
+    let mut args = vec![];
+    for (i, param) in body.params.iter().enumerate() {
+        if let Some(param) = func.index_map.get(&i) {
+            args.push(param.name.clone());
+        } else if i == 0 && sig.decl.implicit_self.has_implicit_self() {
+            params.push(source_map.span_to_snippet(param.span).unwrap());
+            args.push("self".to_string());
+        } else {
+            let ty = source_map.span_to_snippet(sig.decl.inputs[i].span).unwrap();
+            params.push(format!("a___{}: {}", i, ty));
+            args.push(format!("a___{}", i));
         }
-        map
     }
+    let orig = match sig.decl.output {
+        FnRetTy::Return(ty) => Some(source_map.span_to_snippet(ty.span).unwrap()),
+        FnRetTy::DefaultReturn(_) => None,
+    };
+    let rv = orig.is_some().then(|| "rv___".to_string());
+    let binding = if rv.is_some() { "let rv___ = " } else { "" };
+    let vis = source_map.span_to_snippet(vis_span).unwrap();
+    let vis = if vis.is_empty() { vis } else { vis + " " };
+    let unsafety = if sig.header.is_unsafe() {
+        "unsafe "
+    } else {
+        ""
+    };
+    let callee = if tcx.impl_of_method(def_id).is_some() {
+        format!("Self::{}_raw", name)
+    } else {
+        format!("{}_raw", name)
+    };
+    let local_vars: String = func.params().map(|param| param.local_vars()).collect();
+    let wrapper = format!(
+        "
+{}{}fn {}({}) -> {} {{{}
+    {}{}({});
+    {}
+}}",
+        vis,
+        unsafety,
+        name,
+        params.join(", "),
+        func.return_type(orig),
+        local_vars,
+        binding,
+        callee,
+        args.join(", "),
+        func.return_value(rv),
+    );
+    indent_like(&wrapper, vis_span, source_map)
+}
 
-    fn call_assign(&self, args: &[Arg], assign_map: &BTreeMap<usize, String>) -> String {
-        let mut assigns = vec![];
-        for i in &self.remaining_return {
-            let arg = &args[*i];
-            let param = &self.index_map[i];
-            let set_flag = if let Some(arg) = assign_map.get(i) {
-                format!("{}___s = true;", arg)
-            } else {
-                "".to_string()
+/// A function with the original signature of `def_id` that calls the transformed one, renamed
+/// to `{name}_inner`, and writes its results through the pointers.
+fn keep_wrapper(
+    def_id: DefId,
+    func: &Func,
+    sig: &FnSig<'_>,
+    body: &hir::Body<'_>,
+    span: Span,
+    no_mangle: bool,
+    tcx: TyCtxt<'_>,
+) -> String {
+    let source_map = tcx.sess.source_map();
+    let name = tcx.item_name(def_id);
+    let mut params = vec![];
+    let mut args = vec![];
+    for (i, param) in body.params.iter().enumerate() {
+        let ty = source_map.span_to_snippet(sig.decl.inputs[i].span).unwrap();
+        let (param, arg) = if let Some(param) = func.index_map.get(&i) {
+            (format!("{}: {}", param.name, ty), param.name.clone())
+        } else if i == 0 && sig.decl.implicit_self.has_implicit_self() {
+            let param = source_map.span_to_snippet(param.span).unwrap();
+            (param, "self".to_string())
+        } else {
+            (format!("a___{}: {}", i, ty), format!("a___{}", i))
+        };
+        params.push(param);
+        args.push(Arg {
+            span: DUMMY_SP,
+            code: arg,
+            borrowed: None,
+            dead: false,
+        });
+    }
+    let kept: Vec<_> = args
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !func.index_map.contains_key(i))
+        .map(|(_, arg)| arg.code.as_str())
+        .collect();
+    let ret = match sig.decl.output {
+        FnRetTy::Return(ty) => format!(" -> {}", source_map.span_to_snippet(ty.span).unwrap()),
+        FnRetTy::DefaultReturn(_) => "".to_string(),
+    };
+    let ident = tcx.def_ident_span(def_id).unwrap();
+    let header = source_map
+        .span_to_snippet(span.with_hi(ident.lo()))
+        .unwrap();
+    let callee = if tcx.impl_of_method(def_id).is_some() {
+        format!("Self::{}_inner", name)
+    } else {
+        format!("{}_inner", name)
+    };
+    let assign_map = BTreeMap::new();
+    let mtch = func.call_match(&args, &assign_map);
+    let mut call = func.call_binding(&args, false);
+    if mtch.is_some() {
+        call = "(match ".to_string() + &call;
+    }
+    call += &format!("{}({})", callee, kept.join(", "));
+    call += &func.call_assign(&args, &assign_map, false);
+    if let Some(m) = &mtch {
+        call += m;
+        call += ")";
+    }
+    let wrapper = format!(
+        "
+{}{}{}({}){} {{
+    {}
+}}",
+        if no_mangle { "#[no_mangle]\n" } else { "" },
+        header,
+        name,
+        params.join(", "),
+        ret,
+        call,
+    );
+    indent_like(&wrapper, span, source_map)
+}
+
+/// Prefixes each line of `code` with a newline and the indentation of `span`.
+fn indent_like(code: &str, span: Span, source_map: &SourceMap) -> String {
+    let indent = " ".repeat(source_map.lookup_char_pos(span.lo()).col_display);
+    code.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("\n{}{}", indent, line)
+            }
+        })
+        .collect()
+}
+
+/// Checks whether the pointee of `ty` implements `Default` in the body of `def_id`.
+fn implements_default<'tcx>(ty: Ty<'tcx>, def_id: DefId, tcx: TyCtxt<'tcx>) -> bool {
+    let default = some_or!(tcx.get_diagnostic_item(sym::Default), return false);
+    let ty = ty.builtin_deref(true).unwrap().ty;
+    let param_env = tcx.param_env(def_id);
+    tcx.infer_ctxt()
+        .build()
+        .type_implements_trait(default, [ty], param_env)
+        .must_apply_modulo_regions()
+}
+
+/// `FooOut` for `foo` and `SFooOut` for `S::foo`.
+fn out_struct_name(def_id: DefId, tcx: TyCtxt<'_>) -> String {
+    let camel = |name: &str| -> String {
+        name.split('_')
+            .flat_map(|part| {
+                let mut chars = part.chars();
+                chars
+                    .next()
+                    .into_iter()
+                    .flat_map(char::to_uppercase)
+                    .chain(chars)
+            })
+            .collect()
+    };
+    let mut name = String::new();
+    if let Some(impl_id) = tcx.impl_of_method(def_id) {
+        if let Some(adt) = tcx.type_of(impl_id).skip_binder().ty_adt_def() {
+            name += &camel(tcx.item_name(adt.did()).as_str());
+        }
+    }
+    name += &camel(tcx.item_name(def_id).as_str());
+    name + "Out"
+}
+
+#[derive(Clone, Copy)]
+struct FnItem<'tcx> {
+    def_id: DefId,
+    span: Span,
+    sig: &'tcx FnSig<'tcx>,
+    body_id: BodyId,
+    vis_span: Span,
+    /// The start of the module-level item containing the function, including its attributes.
+    /// This is the impl block for a method.
+    item_start: Span,
+    /// A method of a trait or a trait impl, whose signature cannot change. Only the calls in it
+    /// are transformed.
+    in_trait: bool,
+}
+
+/// Returns functions and inherent methods. Methods of trait impls are excluded because their
+/// signatures are fixed by the traits.
+fn fn_items(tcx: TyCtxt<'_>) -> Vec<FnItem<'_>> {
+    let hir = tcx.hir();
+    let mut fns = vec![];
+    for id in hir.items() {
+        let item = hir.item(id);
+        let item_start = hir
+            .attrs(item.hir_id())
+            .iter()
+            .map(|attr| attr.span)
+            .fold(item.span, |span, attr| span.to(attr))
+            .shrink_to_lo();
+        match &item.kind {
+            ItemKind::Fn(sig, _, body_id) => {
+                fns.push(FnItem {
+                    def_id: id.owner_id.to_def_id(),
+                    span: item.span,
+                    sig,
+                    body_id: *body_id,
+                    vis_span: item.vis_span,
+                    item_start,
+                    in_trait: false,
+                });
+            }
+            ItemKind::Impl(imp) => {
+                for impl_item_ref in imp.items {
+                    let impl_item = hir.impl_item(impl_item_ref.id);
+                    let ImplItemKind::Fn(sig, body_id) = &impl_item.kind else {
+                        continue;
+                    };
+                    fns.push(FnItem {
+                        def_id: impl_item.owner_id.to_def_id(),
+                        span: impl_item.span,
+                        sig,
+                        body_id: *body_id,
+                        vis_span: impl_item.vis_span,
+                        item_start,
+                        in_trait: imp.of_trait.is_some(),
+                    });
+                }
+            }
+            ItemKind::Trait(.., trait_item_refs) => {
+                for trait_item_ref in *trait_item_refs {
+                    let trait_item = hir.trait_item(trait_item_ref.id);
+                    let TraitItemKind::Fn(sig, TraitFn::Provided(body_id)) = &trait_item.kind
+                    else {
+                        continue;
+                    };
+                    fns.push(FnItem {
+                        def_id: trait_item.owner_id.to_def_id(),
+                        span: trait_item.span,
+                        sig,
+                        body_id: *body_id,
+                        vis_span: trait_item.span.shrink_to_lo(),
+                        item_start,
+                        in_trait: true,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    fns
+}
+
+/// The source of the pointee type of `ty` if it is a local alias of a raw pointer, possibly
+/// through other aliases, defined in the same module as `def_id`.
+fn resolve_ptr_alias(ty: &hir::Ty<'_>, def_id: DefId, tcx: TyCtxt<'_>) -> Option<String> {
+    let TyKind::Path(QPath::Resolved(
+        _,
+        hir::Path {
+            res: Res::Def(DefKind::TyAlias { .. }, alias),
+            ..
+        },
+    )) = ty.kind
+    else {
+        return None;
+    };
+    let alias = alias.as_local()?;
+    let ItemKind::TyAlias(aliased, _) = tcx.hir().expect_item(alias).kind else {
+        return None;
+    };
+    match aliased.kind {
+        TyKind::Ptr(MutTy { ty, .. }) => {
+            // the snippet is valid only in the module of the alias, and that of a type from a
+            // macro may contain metavariables
+            if tcx.parent_module_from_def_id(alias)
+                != tcx.parent_module_from_def_id(def_id.expect_local())
+                || ty.span.from_expansion()
+            {
+                return None;
+            }
+            tcx.sess.source_map().span_to_snippet(ty.span).ok()
+        }
+        TyKind::Path(_) => resolve_ptr_alias(aliased, def_id, tcx),
+        _ => None,
+    }
+}
+
+/// How a raw pointer param is wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    None,
+    NonNull,
+    Option,
+    OptionNonNull,
+}
+
+/// Returns how the type of a param is wrapped and the type inside, which is the pointee for
+/// `NonNull`.
+fn unwrap_ptr_ty<'hir>(ty: &'hir hir::Ty<'hir>, tcx: TyCtxt<'_>) -> (Wrapper, &'hir hir::Ty<'hir>) {
+    let TyKind::Path(QPath::Resolved(None, path)) = ty.kind else {
+        return (Wrapper::None, ty);
+    };
+    let Res::Def(_, def_id) = path.res else {
+        return (Wrapper::None, ty);
+    };
+    let Some(hir::GenericArg::Type(arg)) = path
+        .segments
+        .last()
+        .and_then(|seg| seg.args)
+        .and_then(|args| args.args.first())
+    else {
+        return (Wrapper::None, ty);
+    };
+    if is_non_null(def_id, tcx) {
+        (Wrapper::NonNull, arg)
+    } else if tcx.is_diagnostic_item(sym::Option, def_id) {
+        match unwrap_ptr_ty(arg, tcx) {
+            (Wrapper::NonNull, ty) => (Wrapper::OptionNonNull, ty),
+            _ => (Wrapper::Option, arg),
+        }
+    } else {
+        (Wrapper::None, ty)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Param {
+    must: bool,
+    narrow: bool,
+    /// The param is `&mut T` rather than a raw pointer, so arguments are never null.
+    is_ref: bool,
+    wrapper: Wrapper,
+    /// The param is declared `mut`, e.g., because the function advances it.
+    mutable: bool,
+    writes: Vec<Span>,
+    write_args: BTreeMap<Span, usize>,
+    span: Span,
+    hir_id: HirId,
+    name: String,
+    ty: String,
+    init: InitStrategy,
+    null: Option<&'static str>,
+}
+
+impl Param {
+    /// Declares `{name}___v`, which holds the value, and `{name}`, which points to it.
+    fn local_vars(&self) -> String {
+        let flag = if self.must {
+            "".to_string()
+        } else {
+            format!("let mut {}___s: bool = false; ", self.name)
+        };
+        let ptr = if self.is_ref { "&mut" } else { "*mut" };
+        // a function taking `&mut T` or `NonNull<T>` may be safe
+        let unsafe_block = |code: String| {
+            if self.is_ref || self.wrapper != Wrapper::None {
+                format!("unsafe {{ {} }}", code)
+            } else {
+                code
+            }
+        };
+        let (ty, init, addr) = match self.init {
+            InitStrategy::ZeroInit => (
+                self.ty.clone(),
+                self.null.map_or_else(
+                    || {
+                        unsafe_block(format!(
+                            "std::mem::transmute([0u8; std::mem::size_of::<{}>()])",
+                            self.ty
+                        ))
+                    },
+                    str::to_string,
+                ),
+                format!("&mut {}___v", self.name),
+            ),
+            InitStrategy::MaybeUninit => {
+                let addr = if self.is_ref {
+                    unsafe_block(format!("&mut *{}___v.as_mut_ptr()", self.name))
+                } else {
+                    format!("{}___v.as_mut_ptr()", self.name)
+                };
+                (
+                    format!("std::mem::MaybeUninit<{}>", self.ty),
+                    "std::mem::MaybeUninit::uninit()".to_string(),
+                    addr,
+                )
+            }
+            InitStrategy::DefaultTrait => (
+                self.ty.clone(),
+                "Default::default()".to_string(),
+                format!("&mut {}___v", self.name),
+            ),
+        };
+        let (ptr_ty, addr) = match self.wrapper {
+            Wrapper::None => (format!("{} {}", ptr, self.ty), addr),
+            Wrapper::NonNull => (
+                format!("std::ptr::NonNull<{}>", self.ty),
+                format!("std::ptr::NonNull::new({}).unwrap()", addr),
+            ),
+            Wrapper::Option => (
+                format!("Option<*mut {}>", self.ty),
+                format!("Some({} as *mut _)", addr),
+            ),
+            Wrapper::OptionNonNull => (
+                format!("Option<std::ptr::NonNull<{}>>", self.ty),
+                format!("std::ptr::NonNull::new({})", addr),
+            ),
+        };
+        let mt = if self.mutable { "mut " } else { "" };
+        format!(
+            "
+    {0}let mut {1}___v: {2} = {3}; \
+    let {6}{1}: {4} = {5};",
+            flag, self.name, ty, init, ptr_ty, addr, mt
+        )
+    }
+
+    /// Writes `v` through `arg`, the argument for this param at a call site.
+    fn write_back(&self, arg: &Arg, v: &str, set_flag: &str) -> String {
+        if arg.dead {
+            return set_flag.to_string();
+        }
+        let arg = &arg.code;
+        match self.wrapper {
+            Wrapper::None if arg.contains("&mut ") || self.is_ref => {
+                format!("*({}) = {}; {}", arg, v, set_flag)
+            }
+            Wrapper::None => format!(
+                "if !({0}).is_null() {{ *({0}) = {1}; {2} }}",
+                arg, v, set_flag
+            ),
+            Wrapper::NonNull => format!("*({}).as_ptr() = {}; {}", arg, v, set_flag),
+            Wrapper::Option => format!(
+                "if let Some(p___) = {} {{ *p___ = {}; {} }}",
+                arg, v, set_flag
+            ),
+            Wrapper::OptionNonNull => format!(
+                "if let Some(p___) = {} {{ *p___.as_ptr() = {}; {} }}",
+                arg, v, set_flag
+            ),
+        }
+    }
+
+    /// The value of the param, which must have been written.
+    fn value(&self) -> String {
+        if self.init != InitStrategy::MaybeUninit {
+            format!("{}___v", self.name)
+        } else if self.is_ref || self.wrapper != Wrapper::None {
+            format!("unsafe {{ {}___v.assume_init() }}", self.name)
+        } else {
+            format!("{}___v.assume_init()", self.name)
+        }
+    }
+}
+
+#[allow(unused)]
+struct Func {
+    is_unit: bool,
+    first_return: Option<(SuccValue, usize)>,
+    remaining_return: Vec<usize>,
+    index_map: BTreeMap<usize, Param>,
+    hir_id_map: BTreeMap<HirId, Param>,
+    /// The name of the struct returned instead of a tuple.
+    out_struct: Option<String>,
+}
+
+impl Func {
+    fn params(&self) -> impl Iterator<Item = &Param> {
+        self.index_map.values()
+    }
+
+    fn cmp(&self, op: BinOpKind, target: u128) -> Option<bool> {
+        let (sv, _) = &self.first_return?;
+        let n = match *sv {
+            SuccValue::Int(n) => n,
+            SuccValue::Uint(n) => n as i128,
+            _ => return None,
+        };
+        let m = target as i128;
+        match op {
+            BinOpKind::Eq => Some(n == m),
+            BinOpKind::Ne => Some(n != m),
+            BinOpKind::Lt => Some(n < m),
+            BinOpKind::Le => Some(n <= m),
+            BinOpKind::Gt => Some(n > m),
+            BinOpKind::Ge => Some(n >= m),
+            _ => None,
+        }
+    }
+
+    fn call_binding(&self, args: &[Arg], discarded: bool) -> String {
+        let mut xs = vec![];
+        if !self.is_unit {
+            xs.push(if discarded { "_" } else { "rv___" }.to_string());
+        }
+        for i in &self.remaining_return {
+            if args[*i].dead {
+                xs.push("_".to_string());
+            } else {
+                xs.push(format!("rv___{}", i));
+            }
+        }
+        if let Some(name) = &self.out_struct {
+            let fields = self
+                .field_names()
+                .zip(xs)
+                .map(|(f, x)| format!("{}: {}", f, x));
+            mk_string(fields, &format!("({{ let {} {{ ", name), ", ", " } = ")
+        } else if xs.len() == 1 {
+            format!("({{ let {} = ", xs.pop().unwrap())
+        } else {
+            mk_string(xs.iter(), "({ let (", ", ", ") = ")
+        }
+    }
+
+    fn assign_map(&self, span: Span) -> BTreeMap<usize, String> {
+        let mut map = BTreeMap::new();
+        for p in self.params() {
+            let arg_idx = *some_or!(p.write_args.get(&span), continue);
+            map.insert(arg_idx, p.name.clone());
+        }
+        map
+    }
+
+    fn call_assign(
+        &self,
+        args: &[Arg],
+        assign_map: &BTreeMap<usize, String>,
+        discarded: bool,
+    ) -> String {
+        let mut assigns = vec![];
+        for i in &self.remaining_return {
+            let arg = &args[*i];
+            if arg.dead {
+                continue;
+            }
+            let param = &self.index_map[i];
+            let set_flag = if let Some(arg) = assign_map.get(i) {
+                format!("{}___s = true;", arg)
+            } else {
+                "".to_string()
             };
             let assign = if param.must {
-                if arg.code.contains("&mut ") {
-                    format!("*({}) = rv___{}; {}", arg.code, i, set_flag)
+                let cast = if param.narrow {
+                    format!(" as {}", param.ty)
                 } else {
-                    format!(
-                        "if !({0}).is_null() {{ *({0}) = rv___{1}; {2} }}",
-                        arg.code, i, set_flag
-                    )
-                }
-            } else if arg.code.contains("&mut ") {
+                    "".to_string()
+                };
+                let v = format!("rv___{}{}", i, cast);
+                param.write_back(arg, &v, &set_flag)
+            } else if arg.code.contains("&mut ") || param.is_ref || param.wrapper != Wrapper::None {
                 format!(
-                    "if let Some(v___) = rv___{} {{ *({}) = v___; {} }}",
-                    i, arg.code, set_flag
+                    "if let Some(v___) = rv___{} {{ {} }}",
+                    i,
+                    param.write_back(arg, "v___", &set_flag)
                 )
             } else {
                 format!(
@@ -485,7 +1620,11 @@ impl Func {
             };
             assigns.push(assign);
         }
-        let end = if self.is_unit { " })" } else { " rv___ })" };
+        let end = if self.is_unit || discarded {
+            " })"
+        } else {
+            " rv___ })"
+        };
         mk_string(assigns.iter(), "; ", " ", end)
     }
 
@@ -497,26 +1636,59 @@ impl Func {
         } else {
             "".to_string()
         };
-        let assign = if arg.code.contains("&mut ") {
-            format!("*({}) = v___; {}", arg.code, set_flag)
-        } else {
-            format!(
-                "if !({0}).is_null() {{ *({0}) = v___; {1} }}",
-                arg.code, set_flag
-            )
-        };
+        let assign = self.index_map[first].write_back(arg, "v___", &set_flag);
+        let ok = if arg.dead { "_" } else { "v___" };
         let v = match succ_value {
             SuccValue::Int(v) => v.to_string(),
             SuccValue::Uint(v) => v.to_string(),
             SuccValue::Bool(v) => v.to_string(),
         };
         Some(format!(
-            " {{ Ok(v___) => {{ {} {} }} Err(v___) => v___, }}",
-            assign, v
+            " {{ Ok({}) => {{ {} {} }} Err(v___) => v___, }}",
+            ok, assign, v
+        ))
+    }
+
+    fn field_names(&self) -> impl Iterator<Item = String> + '_ {
+        let rv = if self.is_unit {
+            None
+        } else {
+            Some("rv".to_string())
+        };
+        rv.into_iter().chain(
+            self.remaining_return
+                .iter()
+                .map(|i| self.index_map[i].name.clone()),
+        )
+    }
+
+    fn out_struct_def(&self, orig: Option<String>, vis: &str) -> Option<String> {
+        let name = self.out_struct.as_ref()?;
+        let fields = self
+            .field_names()
+            .zip(self.return_types(orig))
+            .map(|(f, ty)| format!("{}{}: {}", vis, f, ty));
+        Some(mk_string(
+            fields,
+            &format!("{}struct {} {{ ", vis, name),
+            ", ",
+            " }\n",
         ))
     }
 
     fn return_type(&self, orig: Option<String>) -> String {
+        if let Some(name) = &self.out_struct {
+            return name.clone();
+        }
+        let mut tys = self.return_types(orig);
+        if tys.len() == 1 {
+            tys.pop().unwrap()
+        } else {
+            mk_string(tys.iter(), "(", ", ", ")")
+        }
+    }
+
+    fn return_types(&self, orig: Option<String>) -> Vec<String> {
         let mut tys = vec![];
         if let Some((_, i)) = &self.first_return {
             let orig = orig.unwrap();
@@ -528,18 +1700,16 @@ impl Func {
         }
         for i in &self.remaining_return {
             let param = &self.index_map[i];
-            let ty = if param.must {
+            let ty = if param.narrow {
+                "bool".to_string()
+            } else if param.must {
                 param.ty.to_string()
             } else {
                 format!("Option<{}>", param.ty)
             };
             tys.push(ty);
         }
-        if tys.len() == 1 {
-            tys.pop().unwrap()
-        } else {
-            mk_string(tys.iter(), "(", ", ", ")")
-        }
+        tys
     }
 
     fn return_value(&self, orig: Option<String>) -> String {
@@ -548,8 +1718,10 @@ impl Func {
             let orig = orig.unwrap();
             let param = &self.index_map[i];
             let v = format!(
-                "if {0}___s {{ Ok({0}___v) }} else {{ Err({1}) }}",
-                param.name, orig
+                "if {0}___s {{ Ok({1}) }} else {{ Err({2}) }}",
+                param.name,
+                param.value(),
+                orig
             );
             values.push(v);
         } else if let Some(v) = orig {
@@ -557,14 +1729,26 @@ impl Func {
         }
         for i in &self.remaining_return {
             let param = &self.index_map[i];
-            let v = if param.must {
-                format!("{}___v", param.name)
+            let v = if param.narrow {
+                format!("({} != 0)", param.value())
+            } else if param.must {
+                param.value()
             } else {
-                format!("if {0}___s {{ Some({0}___v) }} else {{ None }}", param.name)
+                format!(
+                    "if {0}___s {{ Some({1}) }} else {{ None }}",
+                    param.name,
+                    param.value()
+                )
             };
             values.push(v);
         }
-        if values.len() == 1 {
+        if let Some(name) = &self.out_struct {
+            let fields = self
+                .field_names()
+                .zip(values)
+                .map(|(f, v)| format!("{}: {}", f, v));
+            mk_string(fields, &format!("{} {{ ", name), ", ", " }")
+        } else if values.len() == 1 {
             values.pop().unwrap()
         } else {
             mk_string(values.iter(), "(", ", ", ")")
@@ -583,6 +1767,8 @@ struct Call {
     hir_id: HirId,
     span: Span,
     callee: DefId,
+    /// The first arg is the receiver of a method call, outside the parentheses.
+    method: bool,
     args: Vec<Arg>,
 }
 
@@ -590,12 +1776,20 @@ struct Call {
 struct Arg {
     span: Span,
     code: String,
+    /// The local and the path expression of `&mut x`.
+    borrowed: Option<(HirId, HirId)>,
+    /// The arg borrows a temporary that is never read, so nothing is written back to it.
+    dead: bool,
 }
 
 struct BodyVisitor<'tcx> {
     tcx: TyCtxt<'tcx>,
     returns: Vec<Return>,
     calls: Vec<Call>,
+    /// `let mut` statements whose initializers have no side effects.
+    temporaries: Vec<(HirId, Span)>,
+    /// The path expressions reading each local.
+    uses: BTreeMap<HirId, Vec<HirId>>,
 }
 
 impl<'tcx> BodyVisitor<'tcx> {
@@ -604,8 +1798,36 @@ impl<'tcx> BodyVisitor<'tcx> {
             tcx,
             returns: vec![],
             calls: vec![],
+            temporaries: vec![],
+            uses: BTreeMap::new(),
         }
     }
+
+    /// Finds the temporaries whose every use is a removed arg of a call to be rewritten, e.g.,
+    /// `x` in `let mut x = 0; f(&mut x);`.
+    fn dead_temporaries(&self, funcs: &BTreeMap<DefId, Func>) -> Vec<(HirId, Span)> {
+        let out_uses: BTreeSet<_> = self
+            .calls
+            .iter()
+            .filter(|call| !call.span.from_expansion())
+            .filter_map(|call| Some((call, funcs.get(&call.callee)?)))
+            .flat_map(|(call, func)| {
+                call.args
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| func.index_map.contains_key(i))
+                    .filter_map(|(_, arg)| Some(arg.borrowed?.1))
+            })
+            .collect();
+        self.temporaries
+            .iter()
+            .filter(|(hir_id, _)| {
+                let uses = some_or!(self.uses.get(hir_id), return false);
+                uses.iter().all(|e| out_uses.contains(e))
+            })
+            .copied()
+            .collect()
+    }
 }
 
 impl<'tcx> BodyVisitor<'tcx> {
@@ -624,8 +1846,11 @@ impl<'tcx> BodyVisitor<'tcx> {
         callee: &'tcx Expr<'tcx>,
         args: &'tcx [Expr<'tcx>],
     ) {
-        let path = some_or!(expr_to_path(callee), return);
-        let Res::Def(_, def_id) = path.res else {
+        let ExprKind::Path(qpath) = &callee.kind else {
+            return;
+        };
+        let typeck = self.tcx.typeck(callee.hir_id.owner.def_id);
+        let Res::Def(_, def_id) = typeck.qpath_res(qpath, callee.hir_id) else {
             return;
         };
         if !def_id.is_local() {
@@ -639,6 +1864,43 @@ impl<'tcx> BodyVisitor<'tcx> {
                 Arg {
                     span: arg.span,
                     code,
+                    borrowed: borrowed_local(arg),
+                    dead: false,
+                }
+            })
+            .collect();
+        let call = Call {
+            hir_id: expr.hir_id,
+            span: expr.span,
+            callee: def_id,
+            method: false,
+            args,
+        };
+        self.calls.push(call);
+    }
+
+    fn visit_expr_method_call(
+        &mut self,
+        expr: &'tcx Expr<'tcx>,
+        receiver: &'tcx Expr<'tcx>,
+        args: &'tcx [Expr<'tcx>],
+    ) {
+        let typeck = self.tcx.typeck(expr.hir_id.owner.def_id);
+        let def_id = some_or!(typeck.type_dependent_def_id(expr.hir_id), return);
+        if !def_id.is_local() {
+            return;
+        }
+        let source_map = self.tcx.sess.source_map();
+        // the receiver comes first so that args are indexed like params
+        let args = std::iter::once(receiver)
+            .chain(args)
+            .map(|arg| {
+                let code = source_map.span_to_snippet(arg.span).unwrap();
+                Arg {
+                    span: arg.span,
+                    code,
+                    borrowed: borrowed_local(arg),
+                    dead: false,
                 }
             })
             .collect();
@@ -646,6 +1908,7 @@ impl<'tcx> BodyVisitor<'tcx> {
             hir_id: expr.hir_id,
             span: expr.span,
             callee: def_id,
+            method: true,
             args,
         };
         self.calls.push(call);
@@ -659,10 +1922,31 @@ impl<'tcx> HVisitor<'tcx> for BodyVisitor<'tcx> {
         self.tcx.hir()
     }
 
+    fn visit_stmt(&mut self, stmt: &'tcx Stmt<'tcx>) {
+        if let StmtKind::Local(local) = stmt.kind {
+            if let PatKind::Binding(BindingAnnotation(_, Mutability::Mut), hir_id, _, None) =
+                local.pat.kind
+            {
+                if local.els.is_none() && local.init.map_or(false, is_pure) {
+                    self.temporaries.push((hir_id, stmt.span));
+                }
+            }
+        }
+        rustc_hir::intravisit::walk_stmt(self, stmt);
+    }
+
     fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
         match expr.kind {
+            ExprKind::Path(QPath::Resolved(_, path)) => {
+                if let Res::Local(hir_id) = path.res {
+                    self.uses.entry(hir_id).or_default().push(expr.hir_id);
+                }
+            }
             ExprKind::Ret(e) => self.visit_expr_ret(expr, e),
             ExprKind::Call(callee, args) => self.visit_expr_call(expr, callee, args),
+            ExprKind::MethodCall(_, receiver, args, _) => {
+                self.visit_expr_method_call(expr, receiver, args)
+            }
             _ => {}
         }
         rustc_hir::intravisit::walk_expr(self, expr);
@@ -721,34 +2005,151 @@ impl SuccValue {
         }
     }
 
-    fn find(params: &[OutputParam]) -> Option<(Self, usize)> {
+    fn find(params: &[OutputParam], sentinel: Option<i128>) -> Option<(Self, usize)> {
         params.iter().find_map(|param| {
-            if !param.must {
-                Some((Self::from(&param.return_values)?, param.index))
-            } else {
-                None
+            if param.must {
+                return None;
+            }
+            let sv = Self::from(&param.return_values)?;
+            if let Some(sentinel) = sentinel {
+                let matches = match sv {
+                    Self::Int(n) => n == sentinel,
+                    Self::Uint(n) => i128::try_from(n).map_or(false, |n| n == sentinel),
+                    Self::Bool(_) => false,
+                };
+                if !matches {
+                    return None;
+                }
             }
+            Some((sv, param.index))
         })
     }
 }
 
-fn expr_to_path<'a, 'tcx>(expr: &'a Expr<'tcx>) -> Option<&'a rustc_hir::Path<'tcx>> {
-    if let ExprKind::Path(QPath::Resolved(_, path)) = expr.kind {
-        Some(path)
-    } else {
-        None
-    }
-}
-
-fn remove_cast<'a, 'tcx>(expr: &'a Expr<'tcx>) -> &'a Expr<'tcx> {
-    if let ExprKind::Cast(expr, _) | ExprKind::DropTemps(expr) = expr.kind {
-        remove_cast(expr)
-    } else {
-        expr
+/// Checks whether the pointer in `local` (or a pointer derived from it) is stored anywhere other
+/// than a local variable, e.g., into a global or a struct field, directly or by a callee.
+fn escapes<'tcx>(
+    body: &Body<'tcx>,
+    local: Local,
+    tcx: TyCtxt<'tcx>,
+    visited: &mut BTreeSet<(DefId, Local)>,
+) -> bool {
+    if !visited.insert((body.source.def_id(), local)) {
+        return false;
     }
-}
-
-fn as_int_lit(expr: &Expr<'_>) -> Option<u128> {
+    let is_alias = |aliases: &BTreeSet<Local>, op: &Operand<'_>| match op {
+        Operand::Copy(p) | Operand::Move(p) => {
+            p.projection.is_empty() && aliases.contains(&p.local)
+        }
+        Operand::Constant(_) => false,
+    };
+    let mut aliases = BTreeSet::new();
+    aliases.insert(local);
+    loop {
+        let len = aliases.len();
+        for bbd in body.basic_blocks.iter() {
+            for stmt in &bbd.statements {
+                let StatementKind::Assign(box (place, rvalue)) = &stmt.kind else {
+                    continue;
+                };
+                let uses = match rvalue {
+                    Rvalue::Use(op) | Rvalue::Cast(_, op, _) => is_alias(&aliases, op),
+                    Rvalue::Aggregate(_, ops) => ops.iter().any(|op| is_alias(&aliases, op)),
+                    Rvalue::Ref(_, _, p) | Rvalue::AddressOf(_, p)
+                        if aliases.contains(&p.local) =>
+                    {
+                        // the pointer itself can be copied anywhere through its address
+                        if p.projection.first() != Some(&ProjectionElem::Deref) {
+                            return true;
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                if !uses {
+                    continue;
+                }
+                if !place.projection.is_empty() {
+                    return true;
+                }
+                aliases.insert(place.local);
+            }
+
+            let TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                ..
+            } = &bbd.terminator().kind
+            else {
+                continue;
+            };
+            let indices: Vec<_> = args
+                .iter()
+                .enumerate()
+                .filter_map(|(i, arg)| is_alias(&aliases, arg).then_some(i))
+                .collect();
+            if indices.is_empty() {
+                continue;
+            }
+            let callee_escapes = match func.const_fn_def() {
+                Some((def_id, _)) if def_id.is_local() => {
+                    !tcx.is_mir_available(def_id)
+                        || indices.iter().any(|i| {
+                            let body = tcx.optimized_mir(def_id);
+                            escapes(body, Local::from_usize(i + 1), tcx, visited)
+                        })
+                }
+                // the standard library does not keep pointers, unlike foreign functions
+                Some((def_id, _)) => tcx.is_foreign_item(def_id),
+                None => true,
+            };
+            // the callee may return the pointer
+            if callee_escapes || !destination.projection.is_empty() {
+                return true;
+            }
+            aliases.insert(destination.local);
+        }
+        if aliases.len() == len {
+            return false;
+        }
+    }
+}
+
+/// Returns the local and the path expression of `&mut x`, possibly cast to a raw pointer.
+fn borrowed_local(expr: &Expr<'_>) -> Option<(HirId, HirId)> {
+    let ExprKind::AddrOf(BorrowKind::Ref, Mutability::Mut, e) = remove_cast(expr).kind else {
+        return None;
+    };
+    let ExprKind::Path(QPath::Resolved(_, path)) = e.kind else {
+        return None;
+    };
+    let Res::Local(hir_id) = path.res else {
+        return None;
+    };
+    Some((hir_id, e.hir_id))
+}
+
+/// Checks whether evaluating `expr` has no side effects, e.g., a literal.
+fn is_pure(expr: &Expr<'_>) -> bool {
+    match expr.kind {
+        ExprKind::Lit(_) | ExprKind::Path(_) => true,
+        ExprKind::Unary(UnOp::Neg | UnOp::Not, e) | ExprKind::Cast(e, _) => is_pure(e),
+        ExprKind::Tup(es) | ExprKind::Array(es) => es.iter().all(is_pure),
+        ExprKind::Struct(_, fields, None) => fields.iter().all(|f| is_pure(f.expr)),
+        _ => false,
+    }
+}
+
+fn remove_cast<'a, 'tcx>(expr: &'a Expr<'tcx>) -> &'a Expr<'tcx> {
+    if let ExprKind::Cast(expr, _) | ExprKind::DropTemps(expr) = expr.kind {
+        remove_cast(expr)
+    } else {
+        expr
+    }
+}
+
+fn as_int_lit(expr: &Expr<'_>) -> Option<u128> {
     if let ExprKind::Lit(lit) = remove_cast(expr).kind {
         if let LitKind::Int(n, _) = lit.node {
             return Some(n);
@@ -768,6 +2169,16 @@ fn get_parent(hir_id: HirId, tcx: TyCtxt<'_>) -> Option<&Expr<'_>> {
     }
 }
 
+fn is_discarded(hir_id: HirId, tcx: TyCtxt<'_>) -> bool {
+    matches!(
+        tcx.hir().find_parent(hir_id),
+        Some(Node::Stmt(Stmt {
+            kind: StmtKind::Semi(_),
+            ..
+        }))
+    )
+}
+
 fn get_parent_return(hir_id: HirId, tcx: TyCtxt<'_>) -> Option<&Expr<'_>> {
     let parent = get_parent(hir_id, tcx)?;
     if let ExprKind::Ret(_) = parent.kind {
@@ -813,12 +2224,29 @@ fn get_if_cmp_call(hir_id: HirId, span: Span, tcx: TyCtxt<'_>) -> Option<IfCmpCa
     Some(call)
 }
 
-fn to_comma(span: Span, source_map: &SourceMap) -> Span {
-    if source_map.span_look_ahead(span, ",", Some(1)).is_some() {
-        span.with_hi(span.hi() + BytePos(1))
-    } else {
-        span
+/// The span to remove the `i`th of the params or args in `spans`, together with a separating
+/// comma, when all of those in `removed` are removed. Those before `first` are not in the
+/// parentheses, e.g., the receiver of a method call.
+fn removal_span(
+    spans: &[Span],
+    i: usize,
+    first: usize,
+    removed: &BTreeSet<usize>,
+    source_map: &SourceMap,
+) -> Span {
+    let mut span = spans[i];
+    if i + 1 < spans.len() {
+        // up to the next one
+        span = span.with_hi(spans[i + 1].lo());
+    } else if source_map.span_look_ahead(span, ",", Some(1)).is_some() {
+        span = span.with_hi(span.hi() + BytePos(1));
+    }
+    // the first of the removed ones at the end also removes the comma after the last kept one
+    let trailing = (i..spans.len()).all(|j| removed.contains(&j));
+    if trailing && i > first && !removed.contains(&(i - 1)) {
+        span = span.with_lo(spans[i - 1].hi());
     }
+    span
 }
 
 fn mk_string<S: AsRef<str>, I: Iterator<Item = S>>(
@@ -837,3 +2265,1604 @@ fn mk_string<S: AsRef<str>, I: Iterator<Item = S>>(
     s.push_str(end);
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &str) -> String {
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        transform_code(code, &params, &TransformConfig::default()).unwrap()
+    }
+
+    fn type_checks(code: &str) -> bool {
+        let input = compile_util::str_to_input(code);
+        let config = compile_util::make_config(input);
+        compile_util::run_compiler(config, |tcx| tcx.analysis(()).is_ok()).unwrap_or(false)
+    }
+
+    /// Builds `code` into an executable named `name` under `dir`.
+    fn compile_exe(code: &str, name: &str, dir: &Path) -> PathBuf {
+        let src = dir.join(format!("{}.rs", name));
+        std::fs::write(&src, code).unwrap();
+        let rustc = Path::new(&compile_util::sys_root())
+            .join("bin")
+            .join("rustc");
+        let status = std::process::Command::new(rustc)
+            .arg("--edition=2021")
+            .arg("-Awarnings")
+            .arg("-o")
+            .arg(dir.join(name))
+            .arg(&src)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to compile {:?}", src);
+        dir.join(name)
+    }
+
+    fn run_exe(exe: &Path, args: &[String]) -> (bool, String) {
+        let output = std::process::Command::new(exe).args(args).output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.success(), stdout)
+    }
+
+    /// Compares the transformed code with `snapshots/<name>.snap`. Set `UPDATE_SNAPSHOTS` to
+    /// (re)write the snapshot instead.
+    fn check_snapshot(name: &str, code: &str) {
+        let transformed = run(code);
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("snapshots")
+            .join(format!("{}.snap", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, transformed).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("missing snapshot {:?}", path));
+        assert_eq!(transformed, expected, "snapshot {} differs", name);
+    }
+
+    #[test]
+    fn test_keys() {
+        let code = "
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                g(&mut x);
+                x
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params.keys().collect::<Vec<_>>(), vec!["g"]);
+        let transformed = run(code);
+        assert!(transformed.contains("fn g()"));
+        assert!(!transformed.contains("g(&mut x)"));
+    }
+
+    #[test]
+    fn test_nested_module() {
+        let code = "
+            mod m {
+                pub unsafe fn g(p: *mut i32) {
+                    *p = 1;
+                }
+            }
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                m::g(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params.keys().collect::<Vec<_>>(), vec!["m::g"]);
+        let transformed = run(code);
+        assert!(transformed.contains("fn g()"));
+        assert!(transformed.contains("m::g()"));
+        assert!(!transformed.contains("m::g(&mut x)"));
+    }
+
+    #[test]
+    fn test_abi_sensitive() {
+        let code = "
+            #[no_mangle]
+            pub unsafe extern \"C\" fn g(p: *mut i32) {
+                *p = 1;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params["g"][0].abi_sensitive);
+
+        let transformed = transform_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(transformed, code);
+
+        let conf = TransformConfig {
+            allow_abi_break: true,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("fn g()"));
+    }
+
+    #[test]
+    fn test_narrow_bool_outputs() {
+        let code = "
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                g(0, &mut x);
+                x
+            }
+            unsafe fn g(n: i32, p: *mut i32) {
+                if n > 0 {
+                    *p = 1;
+                } else {
+                    *p = 0;
+                }
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params["g"][0].bool_output);
+
+        let conf = TransformConfig {
+            narrow_bool_outputs: true,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("-> bool"));
+        assert!(transformed.contains("as i32"));
+    }
+
+    #[test]
+    fn test_snapshot_must() {
+        let code = "
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                g(&mut x);
+                x
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+        ";
+        check_snapshot("must", code);
+    }
+
+    #[test]
+    fn test_snapshot_two_musts() {
+        let code = "
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                g(&mut x, &mut y);
+                x + y
+            }
+            unsafe fn g(p: *mut i32, q: *mut i32) {
+                *p = 1;
+                *q = 2;
+            }
+        ";
+        check_snapshot("two_musts", code);
+    }
+
+    #[test]
+    fn test_snapshot_may() {
+        let code = "
+            unsafe fn f(b: bool) -> i32 {
+                let mut x = 0;
+                g(b, &mut x);
+                x
+            }
+            unsafe fn g(b: bool, p: *mut i32) {
+                if b {
+                    *p = 1;
+                }
+            }
+        ";
+        check_snapshot("may", code);
+    }
+
+    #[test]
+    fn test_caller_and_callee() {
+        let code = "
+            unsafe fn f(q: *mut i32, r: *mut i32) {
+                let mut x = 0;
+                g(&mut x);
+                g(q);
+                *r = x;
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+            unsafe fn h() -> i32 {
+                let mut y = 0;
+                let mut z = 0;
+                f(&mut y, &mut z);
+                y + z
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params.keys().collect::<Vec<_>>(), vec!["f", "g"]);
+        let transformed = run(code);
+        assert!(transformed.contains("-> (i32, i32)"));
+        assert!(transformed.contains("fn g()"));
+        assert!(!transformed.contains("g(q)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_escaping_param() {
+        let code = "
+            #[derive(Clone, Copy)]
+            struct S {
+                p: *mut i32,
+            }
+            static mut G: S = S { p: 0 as *mut i32 };
+            unsafe fn f(p: *mut i32) {
+                let s = S { p };
+                *p = 1;
+                G = s;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params.contains_key("f"));
+        let transformed = run(code);
+        assert_eq!(transformed, code);
+    }
+
+    #[test]
+    fn test_discarded_result() {
+        let code = "
+            unsafe fn f() -> i32 {
+                let mut x = 0;
+                g(&mut x);
+                let n = g(&mut x);
+                x + n
+            }
+            unsafe fn g(p: *mut i32) -> i32 {
+                *p = 1;
+                return 0;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("({ let (_, rv___0) = g(); *(&mut x) = rv___0;  });"));
+        assert!(transformed.contains("let n = ({ let (rv___, rv___0) = g();"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_error_code_to_result() {
+        let code = "
+            unsafe fn f(n: i32, p: *mut i32) -> i32 {
+                if n < 0 {
+                    return -1;
+                }
+                *p = n;
+                return 0;
+            }
+            unsafe fn g(n: i32) -> i32 {
+                let mut x = 0;
+                if f(n, &mut x) != 0 {
+                    return -1;
+                }
+                return x;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("-> Result<i32, i32>"));
+        assert!(transformed.contains("Ok(p___v.assume_init())"));
+        assert!(transformed.contains("Ok(v___) =>"));
+        assert!(type_checks(&transformed));
+
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let conf = TransformConfig {
+            errno_success: Some(0),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("-> Result<i32, i32>"));
+        assert!(type_checks(&transformed));
+
+        let conf = TransformConfig {
+            errno_success: Some(1),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(!transformed.contains("Result"));
+        assert!(transformed.contains("-> (i32, Option<i32>)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_dead_temporary() {
+        let code = "
+            unsafe fn f(p: *mut i32, q: *mut i32) {
+                *p = 1;
+                *q = 2;
+            }
+            unsafe fn k() -> i32 {
+                3
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                let mut y: i32 = -1;
+                let mut z = k();
+                f(&mut x, &mut y);
+                f(&mut z, &mut y as *mut i32);
+                z
+            }
+        ";
+        let transformed = run(code);
+        assert!(!transformed.contains("let mut x"), "{}", transformed);
+        assert!(!transformed.contains("let mut y"), "{}", transformed);
+        assert!(transformed.contains("let mut z = k();"), "{}", transformed);
+        assert!(transformed.contains("let (_, _) = f();"), "{}", transformed);
+        assert!(
+            transformed.contains("*(&mut z) = rv___0;"),
+            "{}",
+            transformed
+        );
+        assert!(!transformed.contains("rv___1"), "{}", transformed);
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_dead_temporary_result() {
+        let code = "
+            unsafe fn f(n: i32, p: *mut i32) -> i32 {
+                if n < 0 {
+                    return -1;
+                }
+                *p = n;
+                return 0;
+            }
+            unsafe fn g(n: i32) -> i32 {
+                let mut x = 0;
+                if f(n, &mut x) != 0 {
+                    return -1;
+                }
+                let mut y = 0;
+                return f(n, &mut y);
+            }
+        ";
+        let transformed = run(code);
+        assert!(!transformed.contains("let mut x"), "{}", transformed);
+        assert!(!transformed.contains("let mut y"), "{}", transformed);
+        assert!(!transformed.contains("v___ =>"), "{}", transformed);
+        assert!(transformed.contains("Ok(_) =>"), "{}", transformed);
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_mut_ref() {
+        let code = "
+            fn f(n: i32, p: &mut i32) {
+                *p = n;
+            }
+            fn g(n: i32, p: &mut i32) -> i32 {
+                if n < 0 {
+                    return -1;
+                }
+                *p = n;
+                return 0;
+            }
+            fn h(p: &mut i32) -> i32 {
+                f(1, p);
+                let mut x = 0;
+                if g(2, &mut x) != 0 {
+                    return 0;
+                }
+                return x;
+            }
+        ";
+        let conf = AnalysisConfig {
+            mut_refs: true,
+            ..AnalysisConfig::default()
+        };
+        let params = analyze_code(code, &conf).unwrap();
+        let transformed = transform_code(code, &params, &TransformConfig::default()).unwrap();
+        assert!(transformed.contains("fn f(n: i32) -> i32"));
+        assert!(transformed.contains("fn g(n: i32) -> Result<i32, i32>"));
+        assert!(transformed.contains("fn h() -> (i32, i32)"));
+        assert!(!transformed.contains("is_null"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_method() {
+        let code = "
+            struct S {
+                n: i32,
+            }
+            impl S {
+                unsafe fn f(&self, m: i32, p: *mut i32) {
+                    *p = self.n + m;
+                }
+            }
+            unsafe trait T {
+                unsafe fn g(&self, p: *mut i32);
+            }
+            unsafe impl T for S {
+                unsafe fn g(&self, p: *mut i32) {
+                    *p = self.n;
+                }
+            }
+            unsafe fn h(s: &S) -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                s.f(1, &mut x);
+                S::f(s, 2, &mut y);
+                s.g(&mut x);
+                return x + y;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("fn f(&self, m: i32) -> i32"));
+        assert!(transformed.contains("s.f(1)"));
+        assert!(transformed.contains("S::f(s, 2)"));
+        assert!(transformed.contains("fn g(&self, p: *mut i32)"));
+        assert!(transformed.contains("s.g(&mut x)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_null_guarded() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                if p.is_null() {
+                    return;
+                }
+                *p = 0;
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 1;
+                f(&mut x);
+                return x;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("fn f() -> i32"));
+        assert!(!transformed.contains("Option"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_init_strategy() {
+        let code = "
+            #[derive(Default)]
+            struct A {
+                x: i32,
+            }
+            struct B {
+                x: i32,
+            }
+            unsafe fn f(p: *mut A, q: *mut B) {
+                *p = A { x: 1 };
+                *q = B { x: 2 };
+            }
+            unsafe fn g() -> i32 {
+                let mut a = A { x: 0 };
+                let mut b = B { x: 0 };
+                f(&mut a, &mut b);
+                return a.x + b.x;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+
+        let conf = TransformConfig {
+            init_strategy: InitStrategy::DefaultTrait,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("let mut p___v: A = Default::default();"));
+        assert!(transformed.contains("let mut q___v: std::mem::MaybeUninit<B> ="));
+        assert!(transformed.contains("(p___v, q___v.assume_init())"));
+        assert!(type_checks(&transformed));
+
+        let conf = TransformConfig {
+            init_strategy: InitStrategy::ZeroInit,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("let mut q___v: B = std::mem::transmute("));
+        assert!(transformed.contains("(p___v, q___v)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_report() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 0;
+            }
+            unsafe fn g(b: bool, p: *mut i32) {
+                if b {
+                    *p = 0;
+                }
+            }
+            #[no_mangle]
+            unsafe extern \"C\" fn h(p: *mut i32) {
+                *p = 0;
+            }
+            unsafe fn k() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                return x;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.functions.keys().collect::<Vec<_>>(), vec!["f", "g"]);
+        assert_eq!(report.functions["g"], vec![1]);
+        assert_eq!(
+            report.warnings,
+            vec![
+                "param 1 of g is not written on every path",
+                "h is not transformed because its ABI is observable",
+            ]
+        );
+        assert_eq!(
+            report.skipped,
+            BTreeMap::from([("h".to_string(), "its ABI is observable".to_string())])
+        );
+        assert!(report.lines_added > 0);
+        assert!(report.lines_removed > 0);
+
+        let transformed = run(code);
+        let grown = transformed.lines().count() - code.lines().count();
+        assert_eq!(grown, report.lines_added - report.lines_removed);
+    }
+
+    #[test]
+    fn test_call_in_macro() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 0;
+            }
+            macro_rules! call_f {
+                ($x:expr) => {
+                    f($x)
+                };
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 1;
+                call_f!(&mut x);
+                return x;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("f($x)"));
+        assert!(transformed.contains("call_f!(&mut x);"));
+        assert!(transformed.contains(
+            "compile_error!(\"nopcrat: cannot transform the call to f at line 12; please update it manually\");"
+        ));
+
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.unrewritten_calls, 1);
+    }
+
+    #[test]
+    fn test_write_back_style() {
+        let code = "
+            unsafe fn get_pair(n: i32, p: *mut i32, q: *mut i32) -> i32 {
+                *p = n;
+                *q = n + 1;
+                return 0;
+            }
+            struct S;
+            impl S {
+                #[inline]
+                pub unsafe fn f(&self, p: *mut i32, q: *mut bool) {
+                    *p = 1;
+                    *q = true;
+                }
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                let mut b = false;
+                get_pair(1, &mut x, &mut y);
+                let r = get_pair(2, &mut x, &mut y);
+                S.f(&mut x, &mut b);
+                return r + x + y;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+
+        let transformed = run(code);
+        assert!(transformed.contains("-> (i32, i32, i32)"));
+        assert!(!transformed.contains("Out"));
+        assert!(type_checks(&transformed));
+
+        let conf = TransformConfig {
+            write_back_style: WriteBackStyle::Struct,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("struct GetPairOut { rv: i32, p: i32, q: i32 }"));
+        assert!(transformed.contains("pub struct SFOut { pub p: i32, pub q: bool }"));
+        assert!(transformed.contains("-> GetPairOut"));
+        assert!(transformed.contains("let GetPairOut { rv: _, p: rv___1, q: rv___2 } ="));
+        assert!(transformed.contains("let GetPairOut { rv: rv___, p: rv___1, q: rv___2 } ="));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_module_file() {
+        let dir = std::env::temp_dir().join(format!("nopcrat-module-file-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("inner")).unwrap();
+        let main = dir.join("main.rs");
+        std::fs::write(
+            &main,
+            "mod inner;\nunsafe fn f() -> i32 {\n    let mut x = 0;\n    inner::deeper::g(&mut x);\n    x\n}\nfn main() {}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("inner.rs"), "pub mod deeper;\n").unwrap();
+        let deeper = dir.join("inner").join("deeper.rs");
+        std::fs::write(&deeper, "pub unsafe fn g(p: *mut i32) {\n    *p = 1;\n}\n").unwrap();
+
+        let params = analyze_path(&main, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params.keys().collect::<Vec<_>>(), vec!["inner::deeper::g"]);
+        let suggestions = collect_suggestions(&main, &params, &TransformConfig::default()).unwrap();
+        let files: Vec<_> = suggestions.keys().cloned().collect();
+        assert_eq!(files, vec![deeper.clone(), main.clone()]);
+
+        apply(&suggestions);
+        let g = std::fs::read_to_string(&deeper).unwrap();
+        assert!(g.contains("fn g() -> i32"));
+        let f = std::fs::read_to_string(&main).unwrap();
+        assert!(f.contains("inner::deeper::g()"));
+
+        // applying the same suggestions again does nothing
+        apply(&suggestions);
+        assert_eq!(std::fs::read_to_string(&deeper).unwrap(), g);
+        assert_eq!(std::fs::read_to_string(&main).unwrap(), f);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reexport() {
+        let code = "
+            mod m {
+                pub unsafe fn f(p: *mut i32) {
+                    *p = 0;
+                }
+            }
+            pub use m::f;
+            use m::f as g;
+            unsafe fn h() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                g(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![
+                "m::f is re-exported at line 7; keep a wrapper with the original signature if it is used outside the crate"
+            ]
+        );
+        assert!(type_checks(&run(code)));
+    }
+
+    #[test]
+    fn test_ptr_wrappers() {
+        let code = "
+            use std::ptr::NonNull;
+            unsafe fn f(p: NonNull<i32>) {
+                *p.as_ptr() = 1;
+            }
+            unsafe fn g(p: Option<*mut i32>) {
+                if let Some(p) = p {
+                    *p = 2;
+                }
+            }
+            unsafe fn h(p: Option<NonNull<i32>>) {
+                if let Some(p) = p {
+                    *p.as_ptr() = 3;
+                }
+            }
+            unsafe fn k() -> i32 {
+                let mut x = 0;
+                f(NonNull::new(&mut x).unwrap());
+                g(Some(&mut x));
+                h(NonNull::new(&mut x));
+                x
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("fn f() -> i32"));
+        assert!(transformed.contains("fn g() -> Option<i32>"));
+        assert!(transformed.contains("fn h() -> Option<i32>"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_ptr_alias() {
+        let code = "
+            type P = *mut i32;
+            type Q = P;
+            type R = * mut i32;
+            unsafe fn f(p: Q, q: R) {
+                *p = 1;
+                *q = 2;
+            }
+            unsafe fn g(p: *const i32) -> i32 {
+                let mut x = 0;
+                f(&mut x, &mut x);
+                x + *p
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("-> (i32, i32)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_reassigned() {
+        let code = "
+            unsafe fn f(mut p: *mut i32, b: bool) {
+                *p = 0;
+                if b {
+                    p = 0 as *mut i32;
+                }
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 0;
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("let mut p: *mut i32"));
+        assert!(transformed.contains("let p: *mut i32"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_same_behavior() {
+        let code = "
+            unsafe fn add(a: i32, b: *mut i32) {
+                *b = a + 1;
+            }
+            fn main() {
+                let a: i32 = std::env::args().nth(1).unwrap().parse().unwrap();
+                let mut b = 0;
+                unsafe {
+                    add(a, &mut b);
+                }
+                println!(\"{}\", b);
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("-> i32"));
+        let dir = std::env::temp_dir().join(format!("nopcrat-behavior-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = compile_exe(code, "add_original", &dir);
+        let transformed = compile_exe(&transformed, "add_transformed", &dir);
+        proptest::proptest!(|(a in -1000i32..1000)| {
+            let args = [a.to_string()];
+            proptest::prop_assert_eq!(run_exe(&original, &args), run_exe(&transformed, &args));
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_skip() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 1;
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 2;
+            }
+            unsafe fn h() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                g(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+
+        let conf = TransformConfig {
+            only: Some(["f".to_string()].into()),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("fn f() -> i32"));
+        assert!(transformed.contains("fn g(p: *mut i32)"));
+        assert!(transformed.contains("g(&mut x)"));
+        assert!(type_checks(&transformed));
+
+        let conf = TransformConfig {
+            skip: ["f".to_string()].into(),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("fn f(p: *mut i32)"));
+        assert!(transformed.contains("f(&mut x)"));
+        assert!(transformed.contains("fn g() -> i32"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_ptr_pointee() {
+        let code = "
+            unsafe fn f(p: *mut *mut i32, q: *mut *const i32) {
+                *p = std::ptr::null_mut();
+                *q = std::ptr::null();
+            }
+            unsafe fn g() -> bool {
+                let mut x = 0 as *mut i32;
+                let mut y = 0 as *const i32;
+                f(&mut x, &mut y);
+                x.is_null() && y.is_null()
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params["f"].len(), 2);
+        assert!(params["f"].iter().all(|p| p.must));
+
+        let transformed = run(code);
+        assert!(transformed.contains("-> (*mut i32, *const i32)"));
+        assert!(type_checks(&transformed));
+
+        for init_strategy in [InitStrategy::ZeroInit, InitStrategy::DefaultTrait] {
+            let conf = TransformConfig {
+                init_strategy,
+                ..TransformConfig::default()
+            };
+            let transformed = transform_code(code, &params, &conf).unwrap();
+            assert!(type_checks(&transformed));
+        }
+        let conf = TransformConfig {
+            init_strategy: InitStrategy::ZeroInit,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("let mut p___v: *mut i32 = std::ptr::null_mut();"));
+        assert!(transformed.contains("let mut q___v: *const i32 = std::ptr::null();"));
+    }
+
+    #[test]
+    fn test_no_transform_calls() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 1;
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let conf = TransformConfig {
+            transform_calls: false,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("fn f() -> i32"), "{}", transformed);
+        assert!(
+            transformed.contains("p___v.assume_init()"),
+            "{}",
+            transformed
+        );
+        assert!(transformed.contains("f(&mut x);"), "{}", transformed);
+        assert!(!transformed.contains("rv___"), "{}", transformed);
+        assert!(run(code).contains("rv___"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = std::env::temp_dir().join(format!("nopcrat-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("main.rs");
+        let code = "unsafe fn f(p: *mut i32) {\n    *p = 1;\n}\nunsafe fn g(p: *mut i32) {\n    *p = 2;\n}\nunsafe fn h() -> i32 {\n    let mut x = 0;\n    f(&mut x);\n    x\n}\n#[no_mangle]\nunsafe extern \"C\" fn e(p: *mut i32) {\n    *p = 3;\n}\nfn main() {}\n";
+        std::fs::write(&main, code).unwrap();
+
+        // the call to `f` is not rewritten, so transforming `f` breaks compilation
+        let params = analyze_path(&main, &AnalysisConfig::default()).unwrap();
+        let conf = TransformConfig {
+            transform_calls: false,
+            ..TransformConfig::default()
+        };
+        let skipped = transform_path_verified(&main, &params, &conf).unwrap();
+        assert_eq!(
+            skipped,
+            BTreeMap::from([
+                ("e".to_string(), "its ABI is observable".to_string()),
+                ("f".to_string(), "the result does not compile".to_string()),
+            ])
+        );
+        let transformed = std::fs::read_to_string(&main).unwrap();
+        assert!(transformed.contains("fn f(p: *mut i32)"));
+        assert!(transformed.contains("fn g() -> i32"));
+        assert!(transformed.contains("f(&mut x)"));
+        assert!(compiles(&main, &conf.compile));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unnamed_struct() {
+        let code = "
+            #[derive(Copy, Clone)]
+            #[repr(C)]
+            pub struct C2RustUnnamed_5 {
+                pub x: i32,
+            }
+            pub type S = C2RustUnnamed_5;
+            unsafe fn f(p: *mut C2RustUnnamed_5, q: *mut S) {
+                *p = C2RustUnnamed_5 { x: 1 };
+                *q = *p;
+            }
+            unsafe fn g() -> i32 {
+                let mut s = C2RustUnnamed_5 { x: 0 };
+                let mut t = s;
+                f(&mut s, &mut t);
+                s.x + t.x
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("-> (C2RustUnnamed_5, S)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_macro_items() {
+        let code = "
+            macro_rules! def_alias {
+                ($n:ident, $t:ty) => {
+                    type $n = *mut $t;
+                };
+            }
+            def_alias!(P, i32);
+            macro_rules! def_fn {
+                ($n:ident) => {
+                    unsafe fn $n(p: *mut i32) {
+                        *p = 0;
+                    }
+                };
+            }
+            def_fn!(g);
+            unsafe fn f(p: P) {
+                *p = 1;
+            }
+            unsafe fn h() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                g(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params.contains_key("g"));
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec!["g is not transformed because it is generated by a macro"]
+        );
+        let transformed = run(code);
+        assert!(transformed.contains("fn f() -> i32"));
+        assert!(transformed.contains("g(&mut x)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_skipped() {
+        let code = "
+            struct S {
+                p: *mut i32,
+            }
+            unsafe fn f(p: *mut i32) {
+                *p = 0;
+                let mut s = S { p: 0 as *mut i32 };
+                s.p = p;
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params["f"].len(), 1);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert!(report.functions.is_empty());
+        assert_eq!(report.skipped["f"], "param 0 escapes");
+        assert_eq!(run(code), code);
+    }
+
+    #[test]
+    fn test_skipped_callee_escape() {
+        let code = "
+            struct S {
+                p: *mut i32,
+            }
+            static mut G: S = S { p: 0 as *mut i32 };
+            unsafe fn h(p: *mut i32) {
+                *p = 0;
+                let mut s = S { p: 0 as *mut i32 };
+                s.p = p;
+                G = s;
+            }
+            unsafe fn f(p: *mut i32) {
+                h(p);
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params["f"].len(), 1);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert!(report.functions.is_empty());
+        assert_eq!(report.skipped["h"], "param 0 escapes");
+        assert_eq!(report.skipped["f"], "param 0 escapes");
+        assert_eq!(run(code), code);
+    }
+
+    #[test]
+    fn test_skipped_address_escape() {
+        let code = "
+            static mut Q: *mut *mut i32 = 0 as *mut *mut i32;
+            unsafe fn f(mut p: *mut i32) {
+                *p = 0;
+                Q = &mut p;
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params["f"].len(), 1);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.skipped["f"], "param 0 escapes");
+    }
+
+    #[test]
+    fn test_skipped_pattern() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 0;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        // e.g., a stale analysis result
+        let code = "
+            unsafe fn f(_: *mut i32) {}
+        ";
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert!(report.functions.is_empty());
+        assert_eq!(
+            report.skipped["f"],
+            "the pattern of param 0 is not a binding"
+        );
+        assert_eq!(
+            transform_code(code, &params, &TransformConfig::default()).unwrap(),
+            code
+        );
+    }
+
+    #[test]
+    fn test_partial_struct() {
+        let code = "
+            #![feature(register_tool)]
+            #![register_tool(nopcrat)]
+            pub struct S {
+                a: i32,
+                b: i32,
+            }
+            pub unsafe fn f(c: bool, #[nopcrat::output_param] p: *mut S) {
+                (*p).a = 1;
+                if c {
+                    (*p).b = 2;
+                }
+            }
+            pub unsafe fn g(#[nopcrat::output_param] p: *mut S) {
+                (*p).a = 1;
+                (*p).b = 2;
+            }
+            pub unsafe fn h() -> i32 {
+                let mut s = S { a: 0, b: 0 };
+                f(true, &mut s);
+                g(&mut s);
+                s.a + s.b
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(!params["f"][0].fully_initialized);
+        assert!(params["g"][0].fully_initialized);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(
+            report.skipped,
+            BTreeMap::from([(
+                "f".to_string(),
+                "param 1 is only partially written".to_string()
+            )])
+        );
+        let transformed = run(code);
+        assert!(transformed.contains("fn f(c: bool, #[nopcrat::output_param] p: *mut S)"));
+        assert!(transformed.contains("f(true, &mut s);"));
+        assert!(!transformed.contains("g(&mut s);"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_callers_in_traits() {
+        let code = "
+            mod a {
+                pub unsafe fn g(p: *mut i32) {
+                    *p = 1;
+                }
+            }
+            struct S;
+            trait T {
+                unsafe fn t(&self) -> i32;
+                unsafe fn u(&self) -> i32 {
+                    let mut x = 0;
+                    a::g(&mut x);
+                    x
+                }
+            }
+            impl T for S {
+                unsafe fn t(&self) -> i32 {
+                    let mut x = 0;
+                    a::g(&mut x);
+                    x
+                }
+            }
+            unsafe extern \"C\" fn e() -> i32 {
+                let mut x = 0;
+                a::g(&mut x);
+                x
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("fn g() -> i32"));
+        assert!(!transformed.contains("g(&mut x)"));
+        assert!(transformed.contains("unsafe fn t(&self) -> i32"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_removed_args() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                *p = 1;
+            }
+            unsafe fn g(p: *mut i32, n: i32, q: *mut i32) {
+                *p = n;
+                *q = n;
+            }
+            unsafe fn h(n: i32, p: *mut i32, q: *mut i32) {
+                *p = n;
+                *q = n;
+            }
+            unsafe fn k() -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                f(&mut x);
+                g(&mut x, 1, &mut y);
+                h(
+                    2,
+                    &mut x,
+                    &mut y,
+                );
+                x + y
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("fn f() -> i32"));
+        assert!(transformed.contains("f()"));
+        assert!(transformed.contains("fn g(n: i32) -> (i32, i32)"));
+        assert!(transformed.contains("g(1)"));
+        assert!(transformed.contains("fn h(n: i32) -> (i32, i32)"));
+        assert!(transformed.contains("h(\n                    2\n                )"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_alias_in_module() {
+        let code = "
+            mod m {
+                #[derive(Clone, Copy)]
+                pub struct S {
+                    pub x: i32,
+                }
+                pub type R = *mut S;
+            }
+            mod n {
+                use super::m::S;
+                pub type P = *mut S;
+                pub type Q = P;
+                pub unsafe fn f(p: Q, q: super::m::R) {
+                    *p = S { x: 1 };
+                    *q = S { x: 2 };
+                }
+                pub unsafe fn g() -> i32 {
+                    let mut s = S { x: 0 };
+                    let mut t = S { x: 0 };
+                    f(&mut s, &mut t);
+                    s.x + t.x
+                }
+            }
+        ";
+        let transformed = run(code);
+        assert!(transformed.contains("-> (S, crate::m::S)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_generate_wrapper() {
+        let code = "
+            #[no_mangle]
+            pub unsafe extern \"C\" fn f(a: i32, p: *mut i32, q: *mut i32) -> i32 {
+                *p = a;
+                *q = 2;
+                return 0;
+            }
+            struct S;
+            impl S {
+                unsafe fn m(&self, p: *mut i32) {
+                    *p = 1;
+                }
+            }
+            unsafe fn may(c: bool, p: *mut i32) {
+                if c {
+                    *p = 1;
+                }
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                let r = f(1, &mut x, &mut y);
+                S.m(&mut x);
+                may(true, &mut x);
+                r + x + y
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let conf = TransformConfig {
+            generate_wrapper: true,
+            ..TransformConfig::default()
+        };
+        let report = report_code(code, &params, &conf).unwrap();
+        assert_eq!(report.skipped.keys().collect::<Vec<_>>(), vec!["may"]);
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains(
+            "#[export_name = \"f\"]
+            pub unsafe extern \"C\" fn f_raw(a: i32, p: *mut i32, q: *mut i32) -> i32 {
+                *p = a;"
+        ));
+        assert!(transformed.contains("pub unsafe fn f(a___0: i32) -> (i32, i32, i32) {"));
+        assert!(transformed.contains("let rv___ = f_raw(a___0, p, q);"));
+        assert!(transformed.contains("unsafe fn m(&self) -> i32 {"));
+        assert!(transformed.contains("Self::m_raw(self, p);"));
+        assert!(transformed.contains("= f(1);"));
+        assert!(transformed.contains("may(true, &mut x);"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_keep_wrapper() {
+        let code = "
+            #[no_mangle]
+            pub unsafe extern \"C\" fn f(a: i32, p: *mut i32, q: *mut i32) -> i32 {
+                *p = a;
+                *q = 2;
+                return 0;
+            }
+            struct S;
+            impl S {
+                unsafe fn m(&self, p: *mut i32) {
+                    *p = 1;
+                }
+            }
+            unsafe fn may(c: bool, p: *mut i32) -> i32 {
+                if c {
+                    *p = 1;
+                    return 0;
+                }
+                return 1;
+            }
+            unsafe fn g() -> i32 {
+                let mut x = 0;
+                let mut y = 0;
+                let r = f(1, &mut x, &mut y);
+                S.m(&mut x);
+                r + may(true, &mut x) + x + y
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let conf = TransformConfig {
+            keep_wrapper: true,
+            errno_success: Some(0),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("pub unsafe fn f_inner(a: i32) -> (i32, i32, i32) {"));
+        assert!(transformed.contains(
+            "#[no_mangle]
+            pub unsafe extern \"C\" fn f(a___0: i32, p: *mut i32, q: *mut i32) -> i32 {"
+        ));
+        assert!(transformed.contains("= f_inner(a___0);"));
+        assert!(transformed.contains("unsafe fn m_inner(&self) -> i32"));
+        assert!(transformed.contains("unsafe fn m(&self, p: *mut i32) {"));
+        assert!(transformed.contains("unsafe fn may_inner(c: bool) -> Result<i32, i32> {"));
+        assert!(transformed.contains("unsafe fn may(a___0: bool, p: *mut i32) -> i32 {"));
+        assert!(transformed.contains("let r = f(1, &mut x, &mut y);"));
+        assert!(transformed.contains("S.m(&mut x);"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_phantom_data() {
+        let code = "
+            use std::marker::PhantomData;
+            #[derive(Clone, Copy)]
+            struct S {
+                x: i32,
+                m: PhantomData<*mut u8>,
+            }
+            unsafe fn f(p: *mut S) {
+                *p = S { x: 1, m: PhantomData };
+            }
+            unsafe fn g() -> i32 {
+                let mut s = S { x: 0, m: PhantomData };
+                f(&mut s);
+                s.x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params["f"][0].must);
+        // `PhantomData` is zero-sized, so zero bytes are a valid `S`
+        let conf = TransformConfig {
+            init_strategy: InitStrategy::ZeroInit,
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(transformed.contains("std::mem::size_of::<S>()"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_volatile() {
+        let code = "
+            unsafe fn f(p: *mut i32) {
+                std::ptr::write_volatile(p, 1);
+            }
+            unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params["f"][0].volatile);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.skipped.keys().collect::<Vec<_>>(), vec!["f"]);
+        let transformed = run(code);
+        assert!(transformed.contains("unsafe fn f(p: *mut i32) {"));
+        assert!(!transformed.contains("unsafe fn g(p: *mut i32)"));
+        assert!(transformed.contains("-> i32"));
+    }
+
+    #[test]
+    fn test_module_prefix() {
+        let code = "
+            mod ffi {
+                pub unsafe fn f(p: *mut i32) {
+                    *p = 1;
+                }
+                pub unsafe fn g(p: *mut i32) {
+                    super::native::h(p);
+                }
+            }
+            mod native {
+                pub unsafe fn h(p: *mut i32) {
+                    *p = 1;
+                }
+                pub unsafe fn k() -> i32 {
+                    let mut x = 0;
+                    super::ffi::f(&mut x);
+                    x
+                }
+            }
+        ";
+        let conf = AnalysisConfig {
+            module_prefix: Some("crate::ffi".to_string()),
+            ..AnalysisConfig::default()
+        };
+        let params = analyze_code(code, &conf).unwrap();
+        // `h` is not analyzed, so it may read `*p`
+        assert_eq!(params.keys().collect::<Vec<_>>(), vec!["ffi::f"]);
+
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params.len(), 3);
+        let conf = TransformConfig {
+            module_prefix: Some("crate::ffi".to_string()),
+            ..TransformConfig::default()
+        };
+        let transformed = transform_code(code, &params, &conf).unwrap();
+        assert!(!transformed.contains("f(p: *mut i32)"));
+        assert!(transformed.contains("fn h(p: *mut i32)"));
+        assert!(transformed.contains("super::ffi::f()"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_macro_body() {
+        let code = "
+            macro_rules! with_body {
+                ($name:ident, $p:ident, $b:block) => {
+                    pub unsafe fn $name($p: *mut i32) $b
+                };
+            }
+            with_body!(f, p, { *p = 1; });
+            pub unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+            pub unsafe fn h() -> i32 {
+                let mut x = 0;
+                f(&mut x);
+                g(&mut x);
+                x
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert!(params.contains_key("f"));
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.skipped.keys().collect::<Vec<_>>(), vec!["f"]);
+        let transformed = run(code);
+        assert!(transformed.contains("f(&mut x);"));
+        assert!(!transformed.contains("g(&mut x);"));
+        assert!(type_checks(&transformed));
+
+        let has_braces = |code: &str| {
+            let input = compile_util::str_to_input(code);
+            let config = compile_util::make_config(input);
+            compile_util::run_compiler(config, |tcx| {
+                fn_items(tcx)
+                    .iter()
+                    .map(|item| {
+                        let body = tcx.hir().body(item.body_id);
+                        brace_spans(body, tcx.sess.source_map()).is_some()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap()
+        };
+        let code = "
+            macro_rules! def {
+                () => {
+                    pub unsafe fn f(p: *mut i32) {
+                        *p = 1;
+                    }
+                };
+            }
+            def!();
+            pub unsafe fn g(p: *mut i32) {
+                *p = 1;
+            }
+        ";
+        assert_eq!(has_braces(code), vec![false, true]);
+    }
+
+    #[test]
+    fn test_borrowed_return() {
+        let code = "
+            pub struct S {
+                x: i32,
+                y: i32,
+            }
+            static mut G: i32 = 0;
+            pub unsafe fn f(p: *mut S) -> *mut i32 {
+                (*p).x = 1;
+                (*p).y = 2;
+                return &mut (*p).y;
+            }
+            pub fn g(p: &mut i32) -> &i32 {
+                *p = 1;
+                return unsafe { &G };
+            }
+            pub fn h<'a>(p: &'a mut i32, q: &'a i32) -> &'a i32 {
+                *p = 1;
+                return q;
+            }
+            pub fn i() -> i32 {
+                let mut x = 0;
+                let y = *g(&mut x);
+                *h(&mut x, &y) + x
+            }
+        ";
+        let conf = AnalysisConfig {
+            mut_refs: true,
+            ..AnalysisConfig::default()
+        };
+        let params = analyze_code(code, &conf).unwrap();
+        assert!(!params.contains_key("f"));
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(report.skipped.keys().collect::<Vec<_>>(), vec!["g"]);
+        assert!(report.skipped["g"].contains("borrows from param 0"));
+        let transformed = transform_code(code, &params, &TransformConfig::default()).unwrap();
+        assert!(transformed.contains("fn g(p: &mut i32) -> &i32"));
+        assert!(transformed.contains("fn h<'a>(q: &'a i32) -> (&'a i32, i32)"));
+        assert!(type_checks(&transformed));
+    }
+
+    #[test]
+    fn test_struct_overlap() {
+        let code = "
+            pub struct Child {
+                n: i32,
+            }
+            pub struct Parent {
+                c: Child,
+                m: i32,
+            }
+            pub unsafe fn f(parent: *mut Parent, child: *mut Child) {
+                (*child).n = 1;
+                *parent = Parent { c: Child { n: 2 }, m: 3 };
+            }
+            pub unsafe fn g(p: *mut u8, q: *mut Child) {
+                *p = 1;
+                (*q).n = 2;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        assert_eq!(params["f"].len(), 2);
+        assert_eq!(params["g"].len(), 2);
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![
+                "param 1 of f may point to a field of param 0, \
+                so transforming both may change observable behavior"
+            ]
+        );
+        assert!(type_checks(&run(code)));
+    }
+
+    #[test]
+    fn test_loop_write() {
+        let code = "
+            pub unsafe fn f(p: *mut i32, n: i32) {
+                let mut i = 0;
+                loop {
+                    *p = i;
+                    i += 1;
+                    if i >= n {
+                        break;
+                    }
+                }
+            }
+            pub unsafe fn g(p: *mut i32, n: i32) {
+                let mut i = 0;
+                while i < n {
+                    i += 1;
+                }
+                *p = i;
+            }
+        ";
+        let params = analyze_code(code, &AnalysisConfig::default()).unwrap();
+        let report = report_code(code, &params, &TransformConfig::default()).unwrap();
+        assert_eq!(
+            report.skipped,
+            BTreeMap::from([(
+                "f".to_string(),
+                "param 0 is only written in loops".to_string()
+            )])
+        );
+        let transformed = run(code);
+        assert!(transformed.contains("fn f(p: *mut i32, n: i32)"));
+        assert!(transformed.contains("fn g(n: i32) -> i32"));
+        assert!(type_checks(&transformed));
+    }
+}